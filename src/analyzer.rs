@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+use crate::disassembler;
+
+/// How control gets from one basic block to another
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction
+    Fallthrough,
+    /// 1NNN
+    Jump,
+    /// 2NNN, the block after the call is reached through a fallthrough edge once the subroutine returns
+    Call,
+    /// The next instruction is skipped by 3XKK, 4XKK, 5XY0, 9XY0, EX9E or EXA1
+    Skip,
+}
+
+impl EdgeKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Call => "call",
+            EdgeKind::Skip => "skip",
+        }
+    }
+}
+
+/// Run of instructions that is only ever entered at its first instruction and left after its last one
+#[derive(Clone)]
+pub struct BasicBlock {
+    pub start: u16,
+    /// Address right after the last instruction of the block, 0x10000 for a block at the very end of 64K of memory
+    pub end: usize,
+    pub successors: Vec<(u16, EdgeKind)>,
+}
+
+/// Single line of the analyzed disassembly
+pub enum DisassemblyLine {
+    /// Start of a basic block, subroutine entries are named after their address
+    Label(String),
+    Instruction { address: u16, opcode: u16, mnemonic: String, note: Option<String> },
+    /// Bytes that are never reached by the control flow
+    Data { address: u16, bytes: Vec<u8> },
+}
+
+/// Result of statically following the control flow of a program from its entry point
+#[derive(Clone)]
+pub struct Analysis {
+    pub program_start: usize,
+    pub program_len: usize,
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    /// Addresses of every reachable instruction
+    pub instructions: BTreeSet<u16>,
+    /// Entry addresses of subroutines
+    pub subroutines: BTreeSet<u16>,
+    /// BNNN instructions, their targets depend on V0 so they can't be followed
+    pub computed_jumps: Vec<u16>,
+    /// FX33/FX55 instructions where I is known to point into code, paired with the written address
+    pub self_modifying_writes: Vec<(u16, u16)>,
+}
+
+impl Analysis {
+    /// Follows jumps, calls and skips starting at the program entry point
+    pub fn analyze(memory: &[u8], program_start: usize, program_len: usize) -> Self {
+        let mut analysis = Self {
+            program_start,
+            program_len,
+            blocks: BTreeMap::new(),
+            instructions: BTreeSet::new(),
+            subroutines: BTreeSet::new(),
+            computed_jumps: vec![],
+            self_modifying_writes: vec![],
+        };
+        if program_len == 0 {
+            return analysis;
+        }
+
+        let fetch = |address: u16| (memory[address as usize] as u16) << 8 | memory[address as usize + 1] as u16;
+        let in_memory = |address: u16| (address as usize) + 1 < memory.len();
+
+        // <find reachable instructions>
+        let mut leaders: BTreeSet<u16> = BTreeSet::new();
+        let mut worklist = vec![program_start as u16];
+        leaders.insert(program_start as u16);
+
+        while let Some(mut address) = worklist.pop() {
+            while in_memory(address) && analysis.instructions.insert(address) {
+                let opcode = fetch(address);
+                // nothing follows an instruction at the very end of 64K of memory
+                let next = address.checked_add(2);
+                let skip = next.and_then(|next| next.checked_add(2));
+                let mut targets: Vec<u16> = vec![];
+                let mut falls_through = true;
+
+                match opcode >> 12 {
+                    0 if opcode == 0x00EE => falls_through = false,
+                    1 => {
+                        targets.push(opcode & 0xFFF);
+                        falls_through = false;
+                    },
+                    2 => {
+                        targets.push(opcode & 0xFFF);
+                        analysis.subroutines.insert(opcode & 0xFFF);
+                    },
+                    3 | 4 => targets.extend(skip),
+                    5 | 9 if opcode & 0xF == 0 => targets.extend(skip),
+                    0xB => {
+                        analysis.computed_jumps.push(address);
+                        falls_through = false;
+                    },
+                    0xE if matches!(opcode & 0xFF, 0x9E | 0xA1) => targets.extend(skip),
+                    _ => {}
+                }
+
+                let ends_block = !targets.is_empty() || !falls_through;
+                for target in targets {
+                    leaders.insert(target);
+                    worklist.push(target);
+                }
+                if ends_block && falls_through {
+                    leaders.extend(next);
+                }
+                match next {
+                    Some(next) if falls_through => address = next,
+                    _ => break,
+                }
+            }
+        }
+        // </find reachable instructions>
+
+        // <build basic blocks>
+        let mut block_start: Option<u16> = None;
+        let instructions: Vec<u16> = analysis.instructions.iter().copied().collect();
+        for (i, address) in instructions.iter().copied().enumerate() {
+            let start = *block_start.get_or_insert(address);
+            let next_is_leader = match address.checked_add(2) {
+                Some(next) => leaders.contains(&next) || instructions.get(i + 1) != Some(&next),
+                None => true,
+            };
+            let successors = Analysis::successors(fetch(address), address);
+
+            if successors.len() == 1 && successors[0].1 == EdgeKind::Fallthrough && !next_is_leader {
+                continue;
+            }
+            let successors = successors.into_iter().filter(|(target, _)| in_memory(*target)).collect();
+            analysis.blocks.insert(start, BasicBlock { start, end: address as usize + 2, successors });
+            block_start = None;
+        }
+        // </build basic blocks>
+
+        // <self modifying writes>
+        let mut self_modifying_writes = vec![];
+        for block in analysis.blocks.values() {
+            let mut i_register: Option<u16> = None;
+            for address in (block.start as usize..block.end).step_by(2) {
+                let address = address as u16;
+                let opcode = fetch(address);
+                let x = (opcode & 0xF00) >> 8;
+                match opcode >> 12 {
+                    0xA => i_register = Some(opcode & 0xFFF),
+                    0xF => match opcode & 0xFF {
+                        0x1E | 0x29 | 0x30 | 0x65 => i_register = None,
+                        0x33 | 0x55 => {
+                            let len = if opcode & 0xFF == 0x33 {3} else {x + 1};
+                            if let Some(i_register) = i_register {
+                                let written = (i_register..i_register + len).find(|target| analysis.is_code(*target));
+                                if let Some(target) = written {
+                                    self_modifying_writes.push((address, target));
+                                }
+                            }
+                            if opcode & 0xFF == 0x55 {
+                                i_register = None;
+                            }
+                        },
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+        analysis.self_modifying_writes = self_modifying_writes;
+        // </self modifying writes>
+
+        analysis
+    }
+
+    /// Where control can go after the instruction at the given address
+    /// Targets past the end of the address space are left out.
+    fn successors(opcode: u16, address: u16) -> Vec<(u16, EdgeKind)> {
+        let next = address.checked_add(2);
+        let skip = next.and_then(|next| next.checked_add(2));
+        let target = Some(opcode & 0xFFF);
+        let successors = match opcode >> 12 {
+            0 if opcode == 0x00EE => vec![],
+            1 => vec![(target, EdgeKind::Jump)],
+            2 => vec![(target, EdgeKind::Call), (next, EdgeKind::Fallthrough)],
+            3 | 4 => vec![(next, EdgeKind::Fallthrough), (skip, EdgeKind::Skip)],
+            5 | 9 if opcode & 0xF == 0 => vec![(next, EdgeKind::Fallthrough), (skip, EdgeKind::Skip)],
+            0xB => vec![],
+            0xE if matches!(opcode & 0xFF, 0x9E | 0xA1) => vec![(next, EdgeKind::Fallthrough), (skip, EdgeKind::Skip)],
+            _ => vec![(next, EdgeKind::Fallthrough)],
+        };
+        successors.into_iter().filter_map(|(target, kind)| Some((target?, kind))).collect()
+    }
+
+    /// Returns true if the byte at the given address belongs to a reachable instruction
+    pub fn is_code(&self, address: u16) -> bool {
+        self.instructions.contains(&address) || self.instructions.contains(&address.wrapping_sub(1))
+    }
+
+    fn label(&self, address: u16) -> String {
+        if address as usize == self.program_start {
+            "main".to_owned()
+        }else if self.subroutines.contains(&address) {
+            format!("sub_{:03X}", address)
+        }else{
+            format!("block_{:03X}", address)
+        }
+    }
+
+    fn note(&self, address: u16) -> Option<String> {
+        if self.computed_jumps.contains(&address) {
+            return Some("computed jump".to_owned());
+        }
+        self.self_modifying_writes.iter()
+            .find(|(instruction, _)| *instruction == address)
+            .map(|(_, target)| format!("writes into code at 0x{:03X}", target))
+    }
+
+    /// Disassembles the program, only reachable instructions are decoded and everything else is shown as data
+    pub fn disassembly(&self, memory: &[u8]) -> Vec<DisassemblyLine> {
+        let mut lines = vec![];
+        // counted in usize since a program can fill 64K of memory right up to 0xFFFF
+        let end = (self.program_start + self.program_len).min(memory.len());
+        let mut address = self.program_start;
+        while address < end {
+            if self.instructions.contains(&(address as u16)) && address + 1 < memory.len() {
+                if self.blocks.contains_key(&(address as u16)) {
+                    lines.push(DisassemblyLine::Label(self.label(address as u16)));
+                }
+                let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+                lines.push(DisassemblyLine::Instruction {
+                    address: address as u16,
+                    opcode,
+                    mnemonic: disassembler::disassemble(opcode),
+                    note: self.note(address as u16),
+                });
+                address += 2;
+            }else{
+                let start = address as u16;
+                let mut bytes = vec![];
+                while address < end && !self.instructions.contains(&(address as u16)) && bytes.len() < 8 {
+                    bytes.push(memory[address]);
+                    address += 1;
+                }
+                lines.push(DisassemblyLine::Data { address: start, bytes });
+            }
+        }
+        lines
+    }
+
+    /// Builds the control flow graph in the Graphviz DOT format, blocks containing computed jumps
+    /// or writes into code are highlighted
+    pub fn to_dot(&self, memory: &[u8]) -> String {
+        let mut dot = "digraph rom {\n    node [shape=box fontname=\"monospace\"];\n".to_owned();
+        for block in self.blocks.values() {
+            let mut label = format!("{}:\\l", self.label(block.start));
+            let mut color = "black";
+            for address in (block.start as usize..block.end).step_by(2) {
+                let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+                label.push_str(&format!("{:03X}: {:04X}  {}\\l", address, opcode, disassembler::disassemble(opcode)));
+                if let Some(note) = self.note(address as u16) {
+                    label.push_str(&format!("    ; {}\\l", note));
+                    color = "red";
+                }
+            }
+            dot.push_str(&format!("    \"{:03X}\" [label=\"{}\" color={}];\n", block.start, label, color));
+        }
+        for block in self.blocks.values() {
+            for (target, kind) in block.successors.iter() {
+                dot.push_str(&format!("    \"{:03X}\" -> \"{:03X}\" [label=\"{}\"];\n", block.start, target, kind.name()));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn write_dot(&self, path: &str, memory: &[u8]) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_dot(memory).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4K of memory with the program at 0x200
+    fn analyze(program: &[u8]) -> (Analysis, Vec<u8>) {
+        let mut memory = vec![0u8; 0x1000];
+        memory[0x200..0x200 + program.len()].clone_from_slice(program);
+        (Analysis::analyze(&memory, 0x200, program.len()), memory)
+    }
+
+    #[test]
+    fn follows_calls_skips_and_jumps() {
+        let (analysis, memory) = analyze(&[
+            0x22, 0x08, // CALL 0x208
+            0x30, 0x00, // SE V0, 0x00
+            0x12, 0x0C, // JP 0x20C
+            0x12, 0x06, // JP 0x206, only reached by the skip
+            0x00, 0xEE, // RET
+            0xFF, 0xFF, // data
+            0xB3, 0x00, // JP V0, 0x300
+        ]);
+        assert_eq!(analysis.instructions.iter().copied().collect::<Vec<u16>>(), vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20C]);
+        assert_eq!(analysis.subroutines.iter().copied().collect::<Vec<u16>>(), vec![0x208]);
+        assert_eq!(analysis.computed_jumps, vec![0x20C]);
+
+        let successors = |start: u16| -> Vec<(u16, EdgeKind)> { analysis.blocks[&start].successors.clone() };
+        assert_eq!(successors(0x200), vec![(0x208, EdgeKind::Call), (0x202, EdgeKind::Fallthrough)]);
+        assert_eq!(successors(0x202), vec![(0x204, EdgeKind::Fallthrough), (0x206, EdgeKind::Skip)]);
+        assert_eq!(successors(0x204), vec![(0x20C, EdgeKind::Jump)]);
+        assert_eq!(successors(0x206), vec![(0x206, EdgeKind::Jump)]);
+        assert!(successors(0x208).is_empty());
+        assert!(successors(0x20C).is_empty());
+
+        let data: Vec<(u16, Vec<u8>)> = analysis.disassembly(&memory).into_iter().filter_map(|line| match line {
+            DisassemblyLine::Data { address, bytes } => Some((address, bytes)),
+            _ => None,
+        }).collect();
+        assert_eq!(data, vec![(0x20A, vec![0xFF, 0xFF])]);
+    }
+
+    #[test]
+    fn fallthrough_blocks_end_at_the_next_leader() {
+        // LD V0, 1 falls into the target of the jump at the end
+        let (analysis, _) = analyze(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+        assert_eq!(analysis.blocks.keys().copied().collect::<Vec<u16>>(), vec![0x200, 0x202]);
+        assert_eq!(analysis.blocks[&0x200].end, 0x202);
+        assert_eq!(analysis.blocks[&0x200].successors, vec![(0x202, EdgeKind::Fallthrough)]);
+        assert_eq!(analysis.blocks[&0x202].end, 0x206);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_64k() {
+        let mut memory = vec![0u8; 0x10000];
+        memory[0xFFFA..].clone_from_slice(&[
+            0x30, 0x00, // SE V0, 0x00
+            0xE0, 0x9E, // SKP V0, would skip to 0x10000
+            0x60, 0x00, // LD V0, 0x00, the last instruction in memory
+        ]);
+        let analysis = Analysis::analyze(&memory, 0xFFFA, 6);
+        assert_eq!(analysis.instructions.iter().copied().collect::<Vec<u16>>(), vec![0xFFFA, 0xFFFC, 0xFFFE]);
+        assert_eq!(analysis.blocks[&0xFFFA].successors, vec![(0xFFFC, EdgeKind::Fallthrough), (0xFFFE, EdgeKind::Skip)]);
+        assert_eq!(analysis.blocks[&0xFFFC].successors, vec![(0xFFFE, EdgeKind::Fallthrough)]);
+        assert_eq!(analysis.blocks[&0xFFFE].end, 0x10000);
+        assert!(analysis.blocks[&0xFFFE].successors.is_empty());
+
+        assert_eq!(analysis.disassembly(&memory).iter().filter(|line| matches!(line, DisassemblyLine::Instruction { .. })).count(), 3);
+        assert!(analysis.to_dot(&memory).contains("\"FFFA\" -> \"FFFE\" [label=\"skip\"]"));
+    }
+
+    #[test]
+    fn flags_writes_into_code() {
+        // LD I, 0x204; LD [I], V0 overwrites the jump after it
+        let (analysis, _) = analyze(&[0xA2, 0x04, 0xF0, 0x55, 0x12, 0x04]);
+        assert_eq!(analysis.self_modifying_writes, vec![(0x202, 0x204)]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::postprocess::{self, Frame, PostProcessing};
+
+/// Screenshots are saved here unless a path is given
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+/// File formats screenshots can be saved in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Png,
+    /// Binary portable pixmap, readable by almost anything without needing compression
+    Ppm,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 2] = [ImageFormat::Png, ImageFormat::Ppm];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Ppm => "PPM",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+
+    /// Format a file name asks for by its extension, PNG if it doesn't say
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("ppm") => ImageFormat::Ppm,
+            _ => ImageFormat::Png,
+        }
+    }
+}
+
+/// Current time in UTC as YYYYMMDD-HHMMSS
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {month_index + 3} else {month_index - 9};
+    let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Path of a new capture of the rom, named after the rom and the current time
+pub fn capture_path(rom_path: &str, extension: &str) -> String {
+    let rom_name = Path::new(rom_path).file_stem().and_then(|name| name.to_str()).unwrap_or("chip8");
+    Path::new(SCREENSHOT_DIRECTORY).join(format!("{}-{}.{}", rom_name, timestamp(), extension)).to_string_lossy().into_owned()
+}
+
+/// Writes the frame blown up by the scale, directories leading up to the path are created if needed
+pub fn save_screenshot(path: &str, frame: &Frame, scale: usize, post_processing: &PostProcessing) -> std::io::Result<()> {
+    let scale = scale.clamp(1, postprocess::MAX_SCALE);
+    let pixels = postprocess::scale(frame, scale, post_processing);
+    let (width, height) = (64 * scale as u32, 32 * scale as u32);
+    let bytes = match ImageFormat::from_path(path) {
+        ImageFormat::Png => encode_png(width, height, &pixels),
+        ImageFormat::Ppm => encode_ppm(width, height, &pixels),
+    };
+    if let Some(directory) = Path::new(path).parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, bytes)
+}
+
+pub fn encode_ppm(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    bytes.extend_from_slice(rgb);
+    bytes
+}
+
+/// Encodes RGB pixels as a PNG, the image data is stored without compression
+pub fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolour, no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    png_chunk(&mut bytes, b"IHDR", &header);
+
+    // every row starts with the filter it uses, none here
+    let row_len = width as usize * 3;
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgb.chunks(row_len) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream made up of stored deflate blocks
+    let mut data = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        data.push(blocks.peek().is_none() as u8);
+        data.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        data.extend_from_slice(block);
+    }
+    if raw.is_empty() {
+        data.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    data.extend_from_slice(&adler32(&raw).to_be_bytes());
+    png_chunk(&mut bytes, b"IDAT", &data);
+
+    png_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+fn png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {crc >> 1 ^ 0xEDB88320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    /// Chunks of a PNG as their kind and data, checking the CRC of every one
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc32(&rest[4..8 + len]), crc);
+            chunks.push((rest[4..8].try_into().unwrap(), rest[8..8 + len].to_vec()));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    /// Contents of a zlib stream made up of stored blocks
+    fn inflate_stored(data: &[u8]) -> Vec<u8> {
+        assert_eq!((data[0] as u16) << 8 | data[1] as u16, 0x7801);
+        assert_eq!(((data[0] as u16) << 8 | data[1] as u16) % 31, 0, "header check bits");
+        let mut raw = Vec::new();
+        let mut rest = &data[2..];
+        loop {
+            let last = rest[0] & 1 == 1;
+            assert_eq!(rest[0] >> 1, 0, "stored block");
+            let len = u16::from_le_bytes([rest[1], rest[2]]);
+            assert_eq!(!len, u16::from_le_bytes([rest[3], rest[4]]));
+            raw.extend_from_slice(&rest[5..5 + len as usize]);
+            rest = &rest[5 + len as usize..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(rest, &adler32(&raw).to_be_bytes());
+        raw
+    }
+
+    #[test]
+    fn png_holds_the_pixels_unfiltered() {
+        // wide enough to need more than one stored block
+        let (width, height) = (200u32, 120u32);
+        let rgb: Vec<u8> = (0..width * height * 3).map(|i| (i % 251) as u8).collect();
+        let png = encode_png(width, height, &rgb);
+
+        let chunks = chunks(&png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, vec![b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(&chunks[0].1[..8], &[0, 0, 0, 200, 0, 0, 0, 120]);
+        assert_eq!(&png[png.len() - 4..], &0xAE426082u32.to_be_bytes());
+
+        let raw = inflate_stored(&chunks[1].1);
+        assert!(raw.len() > 0xFFFF);
+        let rows: Vec<&[u8]> = raw.chunks(width as usize * 3 + 1).collect();
+        assert_eq!(rows.len(), height as usize);
+        for (row, pixels) in rows.iter().zip(rgb.chunks(width as usize * 3)) {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], pixels);
+        }
+    }
+}
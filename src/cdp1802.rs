@@ -0,0 +1,412 @@
+/// Everything the CPU is connected to
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    /// Byte put on the bus by OUT 1 to OUT 7
+    fn output(&mut self, port: u8, value: u8);
+    /// Byte read from the bus by INP 1 to INP 7
+    fn input(&mut self, port: u8) -> u8;
+    /// State of the external flag lines EF1 to EF4
+    fn flag(&mut self, line: u8) -> bool;
+}
+
+/// RCA CDP1802 CPU, cycles are counted in machine cycles of 8 clocks each
+#[allow(non_snake_case)]
+#[derive(Clone, Default)]
+pub struct Cdp1802 {
+    /// Scratchpad registers
+    pub R: [u16; 16],
+    pub D: u8,
+    pub DF: bool,
+    /// Designates the program counter
+    pub P: u8,
+    /// Designates the data pointer
+    pub X: u8,
+    /// X and P saved by an interrupt
+    pub T: u8,
+    /// Interrupts enabled
+    pub IE: bool,
+    pub Q: bool,
+    /// Waiting for an interrupt or DMA after IDL
+    pub idle: bool,
+    pub cycles: u64,
+}
+
+impl Cdp1802 {
+    /// State after a reset, the program starts at 0x0000 with R0 as program counter
+    pub fn reset(&mut self) {
+        self.R[0] = 0;
+        self.P = 0;
+        self.X = 0;
+        self.Q = false;
+        self.IE = true;
+        self.idle = false;
+    }
+
+    /// Takes an interrupt if they are enabled, returns true if one was taken
+    pub fn interrupt(&mut self) -> bool {
+        if !self.IE {
+            return false;
+        }
+        self.T = self.X << 4 | self.P;
+        self.P = 1;
+        self.X = 2;
+        self.IE = false;
+        self.idle = false;
+        self.cycles += 1;
+        true
+    }
+
+    /// Single DMA out cycle, returns the byte R0 pointed at
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.R[0]);
+        self.R[0] = self.R[0].wrapping_add(1);
+        self.idle = false;
+        self.cycles += 1;
+        value
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.P as usize;
+        let value = bus.read(self.R[p]);
+        self.R[p] = self.R[p].wrapping_add(1);
+        value
+    }
+
+    fn rx(&self) -> u16 {
+        self.R[self.X as usize]
+    }
+
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.D = sum as u8;
+        self.DF = sum > 0xFF;
+    }
+
+    /// D = a - b, DF is set when there was no borrow
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.D = difference as u8;
+        self.DF = difference >= 0;
+    }
+
+    /// Short branch to the byte following the opcode if the condition holds
+    fn short_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let p = self.P as usize;
+        if condition {
+            let target = bus.read(self.R[p]);
+            self.R[p] = self.R[p] & 0xFF00 | target as u16;
+        }else{
+            self.R[p] = self.R[p].wrapping_add(1);
+        }
+    }
+
+    /// Long branch to the two bytes following the opcode if the condition holds
+    fn long_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let p = self.P as usize;
+        if condition {
+            let high = bus.read(self.R[p]);
+            let low = bus.read(self.R[p].wrapping_add(1));
+            self.R[p] = (high as u16) << 8 | low as u16;
+        }else{
+            self.R[p] = self.R[p].wrapping_add(2);
+        }
+    }
+
+    /// Skips the next two bytes if the condition holds
+    fn long_skip(&mut self, condition: bool) {
+        if condition {
+            let p = self.P as usize;
+            self.R[p] = self.R[p].wrapping_add(2);
+        }
+    }
+
+    /// Executes a single instruction, returns the machine cycles it took
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        if self.idle {
+            self.cycles += 1;
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0xF) as usize;
+        let x = self.X as usize;
+        let mut cycles = 2;
+
+        match opcode >> 4 {
+            0x0 => {
+                if n == 0 { // IDL
+                    self.idle = true;
+                }else{ // LDN
+                    self.D = bus.read(self.R[n]);
+                }
+            },
+            0x1 => self.R[n] = self.R[n].wrapping_add(1), // INC
+            0x2 => self.R[n] = self.R[n].wrapping_sub(1), // DEC
+            0x3 => {
+                let condition = match n & 0x7 {
+                    0 => true,
+                    1 => self.Q,
+                    2 => self.D == 0,
+                    3 => self.DF,
+                    line => bus.flag(line as u8 - 3),
+                };
+                // the upper eight opcodes branch on the inverted condition, 38 (SKP) never branches
+                let condition = if n & 0x8 == 0 {condition} else {!condition};
+                self.short_branch(bus, condition);
+            },
+            0x4 => { // LDA
+                self.D = bus.read(self.R[n]);
+                self.R[n] = self.R[n].wrapping_add(1);
+            },
+            0x5 => bus.write(self.R[n], self.D), // STR
+            0x6 => {
+                match n {
+                    0 => self.R[x] = self.R[x].wrapping_add(1), // IRX
+                    1..=7 => { // OUT
+                        let value = bus.read(self.R[x]);
+                        bus.output(n as u8, value);
+                        self.R[x] = self.R[x].wrapping_add(1);
+                    },
+                    8 => {},
+                    _ => { // INP
+                        self.D = bus.input(n as u8 - 8);
+                        bus.write(self.R[x], self.D);
+                    },
+                }
+            },
+            0x7 => {
+                match n {
+                    0x0 | 0x1 => { // RET, DIS
+                        let value = bus.read(self.R[x]);
+                        self.R[x] = self.R[x].wrapping_add(1);
+                        self.X = value >> 4;
+                        self.P = value & 0xF;
+                        self.IE = n == 0;
+                    },
+                    0x2 => { // LDXA
+                        self.D = bus.read(self.R[x]);
+                        self.R[x] = self.R[x].wrapping_add(1);
+                    },
+                    0x3 => { // STXD
+                        bus.write(self.R[x], self.D);
+                        self.R[x] = self.R[x].wrapping_sub(1);
+                    },
+                    0x4 => { let value = bus.read(self.rx()); self.add(value, self.D, self.DF) }, // ADC
+                    0x5 => { let value = bus.read(self.rx()); self.subtract(value, self.D, !self.DF) }, // SDB
+                    0x6 => { // SHRC
+                        let carry = self.DF;
+                        self.DF = self.D & 1 == 1;
+                        self.D = self.D >> 1 | (carry as u8) << 7;
+                    },
+                    0x7 => { let value = bus.read(self.rx()); self.subtract(self.D, value, !self.DF) }, // SMB
+                    0x8 => bus.write(self.rx(), self.T), // SAV
+                    0x9 => { // MARK
+                        self.T = self.X << 4 | self.P;
+                        bus.write(self.R[2], self.T);
+                        self.X = self.P;
+                        self.R[2] = self.R[2].wrapping_sub(1);
+                    },
+                    0xA => self.Q = false, // REQ
+                    0xB => self.Q = true, // SEQ
+                    0xC => { let value = self.fetch(bus); self.add(value, self.D, self.DF) }, // ADCI
+                    0xD => { let value = self.fetch(bus); self.subtract(value, self.D, !self.DF) }, // SDBI
+                    0xE => { // SHLC
+                        let carry = self.DF;
+                        self.DF = self.D & 0x80 != 0;
+                        self.D = self.D << 1 | carry as u8;
+                    },
+                    _ => { let value = self.fetch(bus); self.subtract(self.D, value, !self.DF) }, // SMBI
+                }
+            },
+            0x8 => self.D = self.R[n] as u8, // GLO
+            0x9 => self.D = (self.R[n] >> 8) as u8, // GHI
+            0xA => self.R[n] = self.R[n] & 0xFF00 | self.D as u16, // PLO
+            0xB => self.R[n] = self.R[n] & 0x00FF | (self.D as u16) << 8, // PHI
+            0xC => {
+                cycles = 3;
+                match n {
+                    0x0 => self.long_branch(bus, true), // LBR
+                    0x1 => self.long_branch(bus, self.Q), // LBQ
+                    0x2 => self.long_branch(bus, self.D == 0), // LBZ
+                    0x3 => self.long_branch(bus, self.DF), // LBDF
+                    0x4 => {}, // NOP
+                    0x5 => self.long_skip(!self.Q), // LSNQ
+                    0x6 => self.long_skip(self.D != 0), // LSNZ
+                    0x7 => self.long_skip(!self.DF), // LSNF
+                    0x8 => self.long_skip(true), // LSKP
+                    0x9 => self.long_branch(bus, !self.Q), // LBNQ
+                    0xA => self.long_branch(bus, self.D != 0), // LBNZ
+                    0xB => self.long_branch(bus, !self.DF), // LBNF
+                    0xC => self.long_skip(self.IE), // LSIE
+                    0xD => self.long_skip(self.Q), // LSQ
+                    0xE => self.long_skip(self.D == 0), // LSZ
+                    _ => self.long_skip(self.DF), // LSDF
+                }
+            },
+            0xD => self.P = n as u8, // SEP
+            0xE => self.X = n as u8, // SEX
+            _ => {
+                // the immediate forms take their operand from the program instead of M(R(X))
+                let value = if n & 0x8 == 0 || n == 0xE {
+                    bus.read(self.rx())
+                }else{
+                    self.fetch(bus)
+                };
+                match n {
+                    0x0 | 0x8 => self.D = value, // LDX, LDI
+                    0x1 | 0x9 => self.D |= value, // OR, ORI
+                    0x2 | 0xA => self.D &= value, // AND, ANI
+                    0x3 | 0xB => self.D ^= value, // XOR, XRI
+                    0x4 | 0xC => self.add(value, self.D, false), // ADD, ADI
+                    0x5 | 0xD => self.subtract(value, self.D, false), // SD, SDI
+                    0x6 => { // SHR
+                        self.DF = self.D & 1 == 1;
+                        self.D >>= 1;
+                    },
+                    0xE => { // SHL
+                        self.DF = self.D & 0x80 != 0;
+                        self.D <<= 1;
+                    },
+                    _ => self.subtract(self.D, value, false), // SM, SMI
+                }
+            },
+        }
+
+        self.cycles += cycles as u64;
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plain RAM with the flag lines held at fixed levels
+    struct TestBus {
+        memory: Vec<u8>,
+        flags: [bool; 4],
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, address: u16) -> u8 {
+            self.memory[address as usize]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.memory[address as usize] = value;
+        }
+
+        fn output(&mut self, _port: u8, _value: u8) {}
+
+        fn input(&mut self, _port: u8) -> u8 {
+            0
+        }
+
+        fn flag(&mut self, line: u8) -> bool {
+            self.flags[line as usize - 1]
+        }
+    }
+
+    /// CPU after a reset with the program loaded at 0x0000
+    fn setup(program: &[u8]) -> (Cdp1802, TestBus) {
+        let mut memory = vec![0u8; 0x100];
+        memory[..program.len()].clone_from_slice(program);
+        let mut cpu = Cdp1802::default();
+        cpu.reset();
+        (cpu, TestBus { memory, flags: [false; 4] })
+    }
+
+    #[test]
+    fn short_branch_only_replaces_the_low_byte_when_taken() {
+        // REQ, BQ 0x10, SEQ, BQ 0x20
+        let (mut cpu, mut bus) = setup(&[0x7A, 0x31, 0x10, 0x7B, 0x31, 0x20]);
+        cpu.R[0] = 0;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.R[0], 0x03, "not taken, the branch byte is skipped");
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.R[0], 0x20);
+    }
+
+    #[test]
+    fn short_branch_on_external_flag() {
+        // B3 0x40
+        let (mut cpu, mut bus) = setup(&[0x36, 0x40]);
+        bus.flags[2] = true;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.R[0], 0x40);
+    }
+
+    #[test]
+    fn long_skip_takes_three_cycles_and_skips_two_bytes() {
+        // LDI 0, LSNZ, LSZ, two skipped bytes, NOP
+        let (mut cpu, mut bus) = setup(&[0xF8, 0x00, 0xC6, 0xCE, 0xFF, 0xFF, 0xC4]);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.R[0], 0x03, "LSNZ doesn't skip while D is 0");
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.R[0], 0x06);
+        assert_eq!(cpu.cycles, 2 + 3 + 3);
+    }
+
+    #[test]
+    fn long_branch_loads_both_bytes() {
+        // LBR 0x0080
+        let (mut cpu, mut bus) = setup(&[0xC0, 0x00, 0x80]);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.R[0], 0x0080);
+    }
+
+    #[test]
+    fn mark_saves_x_and_p_and_ret_restores_them() {
+        // MARK, SEX 2, INC R2, RET
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.memory[0x10..0x14].clone_from_slice(&[0x79, 0xE2, 0x12, 0x70]);
+        cpu.P = 3;
+        cpu.X = 5;
+        cpu.R[3] = 0x10;
+        cpu.R[2] = 0x80;
+        cpu.IE = false;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.T, 0x53);
+        assert_eq!(bus.memory[0x80], 0x53);
+        assert_eq!(cpu.X, 3);
+        assert_eq!(cpu.R[2], 0x7F);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!((cpu.X, cpu.P), (5, 3));
+        assert_eq!(cpu.R[2], 0x81);
+        assert!(cpu.IE);
+    }
+
+    #[test]
+    fn dma_out_reads_through_r0_and_wakes_from_idle() {
+        let (mut cpu, mut bus) = setup(&[0x00]);
+        bus.memory[0x40..0x48].clone_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        cpu.step(&mut bus);
+        assert!(cpu.idle);
+
+        cpu.R[0] = 0x40;
+        let bytes: Vec<u8> = (0..8).map(|_| cpu.dma_out(&mut bus)).collect();
+        assert_eq!(bytes, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(cpu.R[0], 0x48);
+        assert!(!cpu.idle);
+        assert_eq!(cpu.cycles, 2 + 8);
+    }
+
+    #[test]
+    fn interrupt_saves_x_and_p_into_t() {
+        let (mut cpu, _) = setup(&[]);
+        cpu.P = 3;
+        cpu.X = 2;
+        assert!(cpu.interrupt());
+        assert_eq!(cpu.T, 0x23);
+        assert_eq!((cpu.X, cpu.P), (2, 1));
+        assert!(!cpu.interrupt(), "interrupts are disabled until RET");
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::Write;
+
+use crate::disassembler;
+
+/// Counts how many times each memory address was fetched as an instruction
+#[derive(Clone)]
+pub struct Coverage {
+    /// Fetch count for every address in memory
+    pub hits: Vec<u32>,
+    /// Address the program was loaded at
+    pub program_start: usize,
+    /// Length of the loaded program in bytes
+    pub program_len: usize,
+}
+
+impl Coverage {
+    pub fn new(program_start: usize, program_len: usize, memory_len: usize) -> Self {
+        Self { hits: vec![0; memory_len], program_start, program_len }
+    }
+
+    /// Registers a fetch from the given address
+    #[inline]
+    pub fn record(&mut self, address: u16) {
+        if let Some(hits) = self.hits.get_mut(address as usize) {
+            *hits = hits.saturating_add(1);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.hits.iter_mut().for_each(|hits| *hits = 0);
+    }
+
+    /// Walks the program the same way the listing does, returning the address, the length and
+    /// the fetch count of every line. Fetches from odd addresses shift the walk by a single byte
+    /// so instructions that aren't aligned to 2 bytes still get their own line.
+    fn lines(&self) -> Vec<(usize, usize, u32)> {
+        let end = (self.program_start + self.program_len).min(self.hits.len());
+        let mut lines = vec![];
+        let mut address = self.program_start;
+        while address < end {
+            let len = if self.hits[address] == 0 && address + 1 < end && self.hits[address + 1] > 0 {1} else {2};
+            let len = len.min(end - address);
+            lines.push((address, len, self.hits[address]));
+            address += len;
+        }
+        lines
+    }
+
+    /// Returns the amount of executed instructions and the total amount of instructions in the program
+    pub fn summary(&self) -> (usize, usize) {
+        let lines = self.lines();
+        let executed = lines.iter().filter(|(_, _, hits)| *hits > 0).count();
+        (executed, lines.len())
+    }
+
+    pub fn percentage(&self) -> f32 {
+        let (executed, total) = self.summary();
+        if total == 0 {
+            return 0f32;
+        }
+        executed as f32 / total as f32 * 100f32
+    }
+
+    /// Builds a disassembly of the program where every line is prefixed by the amount of times it was executed,
+    /// lines that were never executed are marked with #####
+    pub fn listing(&self, memory: &[u8]) -> String {
+        let (executed, total) = self.summary();
+        let mut listing = format!("; coverage: {}/{} instructions ({:.2}%)\n", executed, total, self.percentage());
+        listing.push_str(";     hits  addr   op    instruction\n");
+
+        for (address, len, hits) in self.lines() {
+            let hits = if hits > 0 {hits.to_string()} else {"#####".to_owned()};
+            if len == 2 {
+                let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+                listing.push_str(&format!("{:>10}  {:03X}:  {:04X}  {}\n", hits, address, opcode, disassembler::disassemble(opcode)));
+            }else{
+                listing.push_str(&format!("{:>10}  {:03X}:  {:02X}    DB 0x{:02X}\n", hits, address, memory[address], memory[address]));
+            }
+        }
+        listing
+    }
+
+    pub fn write_listing(&self, path: &str, memory: &[u8]) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.listing(memory).as_bytes())
+    }
+}
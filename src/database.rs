@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Deserialize;
+use sdl2::keyboard::Keycode;
+
+use crate::palette::Palette;
+use crate::quirks::{Platform, Quirks};
+
+/// Database that ships with the emulator, in the same shape as programs.json of the community chip-8 database.
+/// It only knows the roms bundled in assets/roms, settings for other roms need the community database to be imported.
+const BUILT_IN_DATABASE: &str = include_str!("../assets/programs.json");
+/// An imported database is copied here and used instead of the built-in one on the next start
+pub const IMPORTED_DATABASE_FILE: &str = "rom-database.json";
+
+/// Quirk overrides of a single rom, fields that are missing fall back to the platform's defaults
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+    vblank: Option<bool>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct Colors {
+    /// Background first, then the foreground colours
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RomInfo {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    /// Maps game actions like "up" or "a" to the CHIP-8 key that performs them
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize, Clone)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, RomInfo>,
+}
+
+/// Settings the database recommends for a single rom
+#[derive(Clone)]
+pub struct RomEntry {
+    pub sha1: String,
+    pub title: String,
+    pub description: String,
+    pub authors: Vec<String>,
+    /// Name of the platform as it appears in the database
+    pub platform_name: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// Instructions executed per frame
+    pub tickrate: Option<u32>,
+    /// Palette the rom was made for, XO-CHIP roms can list all four colours
+    pub colors: Option<Palette>,
+    /// CHIP-8 key to host key bindings that replace the default ones
+    pub keys: Vec<(usize, i32)>,
+}
+
+impl RomEntry {
+    /// Binds the database's host keys, a host key that is already bound to another CHIP-8 key
+    /// trades places with it so no host key presses two CHIP-8 keys
+    pub fn apply_keys(&self, keymap: &mut [i32; 16]) {
+        for &(key, host_key) in self.keys.iter() {
+            if let Some(other) = keymap.iter().position(|bound| *bound == host_key) {
+                keymap[other] = keymap[key];
+            }
+            keymap[key] = host_key;
+        }
+    }
+}
+
+/// Known roms keyed by the SHA-1 of the rom file
+#[derive(Clone, Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    /// Lowercase SHA-1 to index into programs
+    hashes: HashMap<String, usize>,
+}
+
+impl Database {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let programs: Vec<Program> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut hashes = HashMap::new();
+        for (i, program) in programs.iter().enumerate() {
+            for sha1 in program.roms.keys() {
+                hashes.insert(sha1.to_lowercase(), i);
+            }
+        }
+        Ok(Self { programs, hashes })
+    }
+
+    /// Loads the imported database if there is one, the built-in database otherwise
+    pub fn load() -> Self {
+        if let Ok(json) = fs::read_to_string(IMPORTED_DATABASE_FILE) {
+            match Database::parse(&json) {
+                Ok(database) => return database,
+                Err(e) => eprintln!("{}: {}", IMPORTED_DATABASE_FILE, e),
+            }
+        }
+        Database::parse(BUILT_IN_DATABASE).unwrap()
+    }
+
+    /// Parses the database file at the given path and keeps a copy of it so it is used on the next start as well
+    pub fn import(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let database = Database::parse(&json)?;
+        fs::write(IMPORTED_DATABASE_FILE, json).map_err(|e| format!("{}: {}", IMPORTED_DATABASE_FILE, e))?;
+        Ok(database)
+    }
+
+    /// Amount of roms in the database
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn hash(program: &[u8]) -> String {
+        sha1_smol::Sha1::from(program).digest().to_string()
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<RomEntry> {
+        let sha1 = Database::hash(program);
+        let program = &self.programs[*self.hashes.get(&sha1)?];
+        let rom = program.roms.iter()
+            .find(|(hash, _)| hash.to_lowercase() == sha1)
+            .map(|(_, rom)| rom.clone())
+            .unwrap_or_default();
+
+        // the first listed platform is the one the rom was made for
+        let platform_name = rom.platforms.first().cloned().unwrap_or_else(|| "originalChip8".to_owned());
+        let platform = Database::platform(&platform_name);
+        let mut quirks = Quirks::preset(platform);
+        if let Some(overrides) = rom.quirky_platforms.get(&platform_name) {
+            if let Some(shift) = overrides.shift { quirks.shifting = shift; }
+            if let Some(jump) = overrides.jump { quirks.jumping = jump; }
+            if let Some(logic) = overrides.logic { quirks.vf_reset = logic; }
+            if let Some(vblank) = overrides.vblank { quirks.display_wait = vblank; }
+            if let Some(wrap) = overrides.wrap { quirks.clipping = !wrap; }
+            if let Some(leave_i_unchanged) = overrides.memory_leave_i_unchanged { quirks.memory_increment = !leave_i_unchanged; }
+            if let Some(true) = overrides.memory_increment_by_x { quirks.memory_increment = true; }
+        }
+
+        let colors = rom.colors.as_ref().and_then(|colors| {
+            // a background and a foreground are needed, the colours of the second plane are optional
+            if colors.pixels.len() < 2 {
+                return None;
+            }
+            let mut palette = Palette::default();
+            for (color, pixel) in palette.colors.iter_mut().zip(colors.pixels.iter()) {
+                *color = Database::parse_color(pixel)?;
+            }
+            Some(palette)
+        });
+
+        let mut keys: Vec<(usize, i32)> = rom.keys.iter()
+            .filter_map(|(action, key)| Some((*key as usize, Database::host_key(action)? as i32)))
+            .filter(|(key, _)| *key < 16)
+            .collect();
+        // the actions come out of a map, sorted they get bound in the same order every time
+        keys.sort();
+
+        Some(RomEntry {
+            sha1,
+            title: program.title.clone(),
+            description: program.description.clone(),
+            authors: program.authors.clone(),
+            platform_name,
+            platform,
+            quirks,
+            tickrate: rom.tickrate,
+            colors,
+            keys,
+        })
+    }
+
+    /// Maps the platform ids used by the database to the platforms the emulator knows about
+    fn platform(name: &str) -> Platform {
+        match name {
+            "chip48" | "superchip1" | "superchip" => Platform::SuperChip,
+            "xochip" => Platform::XoChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    /// Host key used for a game action of the database
+    fn host_key(action: &str) -> Option<Keycode> {
+        match action {
+            "up" => Some(Keycode::Up),
+            "down" => Some(Keycode::Down),
+            "left" => Some(Keycode::Left),
+            "right" => Some(Keycode::Right),
+            "a" => Some(Keycode::Z),
+            "b" => Some(Keycode::X),
+            _ => None,
+        }
+    }
+
+    /// Parses colours written as #RRGGBB
+    fn parse_color(color: &str) -> Option<[u8; 3]> {
+        let color = color.strip_prefix('#')?;
+        if color.len() != 6 || !color.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&color[i..i + 2], 16).ok();
+        Some([channel(0)?, channel(2)?, channel(4)?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_database_knows_the_bundled_rom() {
+        let entry = Database::parse(BUILT_IN_DATABASE).unwrap().lookup(include_bytes!("../assets/roms/hex-font.ch8")).unwrap();
+        assert_eq!(entry.sha1, "3c9cedb77c26161a0cfbd9075de3996310e9d342");
+        assert_eq!(entry.title, "Hex Font");
+        assert_eq!(entry.platform_name, "originalChip8");
+        assert_eq!(entry.platform, Platform::Chip8);
+        assert_eq!(entry.tickrate, Some(15));
+        assert!(entry.quirks.display_wait && entry.quirks.vf_reset && entry.quirks.memory_increment);
+        assert!(!entry.quirks.shifting && !entry.quirks.jumping && entry.quirks.clipping);
+        assert_eq!(entry.colors.unwrap().colors[1], [0x33, 0xFF, 0x33]);
+        assert_eq!(entry.keys, vec![(5, Keycode::Z as i32)]);
+    }
+
+    #[test]
+    fn unknown_roms_are_not_found() {
+        assert!(Database::parse(BUILT_IN_DATABASE).unwrap().lookup(&[0x12, 0x00]).is_none());
+    }
+
+    #[test]
+    fn keys_bound_elsewhere_trade_places() {
+        let entry = Database::parse(BUILT_IN_DATABASE).unwrap().lookup(include_bytes!("../assets/roms/hex-font.ch8")).unwrap();
+        let mut keymap = [0i32; 16];
+        for (i, bound) in keymap.iter_mut().enumerate() {
+            *bound = Keycode::Num0 as i32 + i as i32;
+        }
+        keymap[0xA] = Keycode::Z as i32;
+
+        entry.apply_keys(&mut keymap);
+        assert_eq!(keymap[5], Keycode::Z as i32);
+        assert_eq!(keymap[0xA], Keycode::Num5 as i32);
+        let mut bound = keymap.to_vec();
+        bound.sort();
+        bound.dedup();
+        assert_eq!(bound.len(), 16, "every host key presses a single CHIP-8 key");
+    }
+}
@@ -0,0 +1,220 @@
+use crate::analyzer::Analysis;
+use crate::quirks::{Platform, Quirks};
+
+/// Platform and quirks a program most likely expects, along with why they were picked
+#[derive(Clone)]
+pub struct Detection {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub reasons: Vec<String>,
+}
+
+/// Returns the platform that introduced the given opcode, if it isn't part of the original instruction set
+fn opcode_platform(opcode: u16) -> Option<Platform> {
+    match opcode >> 12 {
+        0 => match opcode & 0xFFF {
+            0x0C0..=0x0CF | 0x0FB..=0x0FF => Some(Platform::SuperChip),
+            0x0D0..=0x0DF => Some(Platform::XoChip),
+            _ => None,
+        },
+        5 if matches!(opcode & 0xF, 2 | 3) => Some(Platform::XoChip),
+        0xD if opcode & 0xF == 0 => Some(Platform::SuperChip),
+        0xF => match opcode & 0xFF {
+            0x30 | 0x75 | 0x85 => Some(Platform::SuperChip),
+            0x01 | 0x3A => Some(Platform::XoChip),
+            0x00 | 0x02 if opcode & 0xF00 == 0 => Some(Platform::XoChip),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fetch(memory: &[u8], address: u16) -> u16 {
+    (memory[address as usize] as u16) << 8 | memory[address as usize + 1] as u16
+}
+
+/// Looks for platform specific opcodes and for code that relies on shift or load/store quirks.
+/// Reachable code is trusted, opcodes only found in unreachable bytes are only taken into account
+/// when the analysis couldn't follow every jump.
+pub fn detect(memory: &[u8], analysis: &Analysis) -> Detection {
+    let mut reasons = vec![];
+    let mut reachable: Vec<(u16, Platform)> = vec![];
+    let mut unreachable: Vec<(u16, Platform)> = vec![];
+
+    let end = (analysis.program_start + analysis.program_len).min(memory.len().saturating_sub(1));
+    for address in (analysis.program_start..end).step_by(2) {
+        let address = address as u16;
+        if let Some(platform) = opcode_platform(fetch(memory, address)) {
+            if analysis.instructions.contains(&address) {
+                reachable.push((address, platform));
+            }else if !analysis.is_code(address) {
+                unreachable.push((address, platform));
+            }
+        }
+    }
+    // instructions that aren't aligned to 2 bytes are missed by the walk above
+    for address in analysis.instructions.iter().filter(|address| *address % 2 == 1) {
+        if let Some(platform) = opcode_platform(fetch(memory, *address)) {
+            reachable.push((*address, platform));
+        }
+    }
+
+    let describe = |found: &[(u16, Platform)], platform: Platform| -> Option<String> {
+        let opcodes: Vec<String> = found.iter()
+            .filter(|(_, p)| *p == platform)
+            .take(4)
+            .map(|(address, _)| format!("{:04X} at 0x{:03X}", fetch(memory, *address), address))
+            .collect();
+        if opcodes.is_empty() {
+            None
+        }else{
+            Some(opcodes.join(", "))
+        }
+    };
+
+    let mut platform = Platform::Chip8;
+    for candidate in [Platform::XoChip, Platform::SuperChip] {
+        if let Some(opcodes) = describe(&reachable, candidate) {
+            reasons.push(format!("Reachable {} opcodes: {}", candidate.name(), opcodes));
+            if platform == Platform::Chip8 {
+                platform = candidate;
+            }
+        }else if let Some(opcodes) = describe(&unreachable, candidate) {
+            if analysis.computed_jumps.is_empty() {
+                reasons.push(format!("Ignoring {} opcodes in unreachable bytes: {}", candidate.name(), opcodes));
+            }else{
+                reasons.push(format!("{} opcodes in bytes the analysis couldn't reach past computed jumps: {}", candidate.name(), opcodes));
+                if platform == Platform::Chip8 {
+                    platform = candidate;
+                }
+            }
+        }
+    }
+    if platform == Platform::Chip8 {
+        reasons.push("No SUPER-CHIP or XO-CHIP opcodes in reachable code".to_owned());
+    }
+
+    let mut quirks = Quirks::preset(platform);
+
+    // <shift quirk>
+    let mut shifts_vy = vec![];
+    let mut shifts_in_place = vec![];
+    for address in analysis.instructions.iter() {
+        let opcode = fetch(memory, *address);
+        if opcode >> 12 == 8 && matches!(opcode & 0xF, 0x6 | 0xE) {
+            let x = (opcode & 0xF00) >> 8;
+            let y = (opcode & 0xF0) >> 4;
+            if y != x && y != 0 {
+                shifts_vy.push(*address);
+            }else if y == 0 && x != 0 {
+                shifts_in_place.push(*address);
+            }
+        }
+    }
+    if shifts_vy.len() > shifts_in_place.len() {
+        quirks.shifting = false;
+        reasons.push(format!("{} shifts use a separate VY (first at 0x{:03X}), so VY gets shifted into VX", shifts_vy.len(), shifts_vy[0]));
+    }else if !shifts_in_place.is_empty() {
+        quirks.shifting = true;
+        reasons.push(format!("{} shifts leave Y at 0 (first at 0x{:03X}), so VX gets shifted in place", shifts_in_place.len(), shifts_in_place[0]));
+    }
+    // </shift quirk>
+
+    // <load/store quirk>
+    // a block that keeps using I after FX55/FX65 without setting it again expects I to have moved past the registers
+    let mut chained_memory_access = vec![];
+    for block in analysis.blocks.values() {
+        let mut after_load_store: Option<u16> = None;
+        for address in (block.start as usize..block.end).step_by(2) {
+            let address = address as u16;
+            let opcode = fetch(memory, address);
+            match (opcode >> 12, opcode & 0xFF) {
+                (0xA, _) | (0xF, 0x1E) | (0xF, 0x29) | (0xF, 0x30) => after_load_store = None,
+                (0xF, 0x55) | (0xF, 0x65) | (0xF, 0x33) | (0xD, _) => {
+                    if let Some(first) = after_load_store {
+                        chained_memory_access.push(first);
+                    }
+                    after_load_store = if matches!(opcode & 0xFF, 0x55 | 0x65) && opcode >> 12 == 0xF {Some(address)} else {None};
+                },
+                _ => {}
+            }
+        }
+    }
+    if !chained_memory_access.is_empty() {
+        quirks.memory_increment = true;
+        reasons.push(format!("I is used again right after FX55/FX65 (first at 0x{:03X}), so those increment I", chained_memory_access[0]));
+    }
+    // </load/store quirk>
+
+    Detection { platform, quirks, reasons }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_program(program: &[u16]) -> Detection {
+        let mut memory = vec![0u8; 0x1000];
+        for (i, opcode) in program.iter().enumerate() {
+            memory[0x200 + i * 2..0x202 + i * 2].clone_from_slice(&opcode.to_be_bytes());
+        }
+        let analysis = Analysis::analyze(&memory, 0x200, program.len() * 2);
+        detect(&memory, &analysis)
+    }
+
+    #[test]
+    fn plain_programs_are_chip8() {
+        let detection = detect_program(&[0x6001, 0x1202]);
+        assert_eq!(detection.platform, Platform::Chip8);
+        assert_eq!(detection.quirks, Quirks::preset(Platform::Chip8));
+        assert_eq!(detection.reasons, vec!["No SUPER-CHIP or XO-CHIP opcodes in reachable code".to_owned()]);
+    }
+
+    #[test]
+    fn super_chip_opcodes_pick_super_chip() {
+        // high resolution, then a large digit
+        let detection = detect_program(&[0x00FF, 0xF030, 0x1204]);
+        assert_eq!(detection.platform, Platform::SuperChip);
+        assert_eq!(detection.quirks, Quirks::preset(Platform::SuperChip));
+        assert!(detection.reasons[0].contains("00FF at 0x200, F030 at 0x202"));
+    }
+
+    #[test]
+    fn xo_chip_opcodes_win_over_super_chip_ones() {
+        let detection = detect_program(&[0x00FF, 0x5012, 0x1204]);
+        assert_eq!(detection.platform, Platform::XoChip);
+        assert_eq!(detection.reasons.len(), 2);
+    }
+
+    #[test]
+    fn unreachable_opcodes_only_count_past_computed_jumps() {
+        let detection = detect_program(&[0x1204, 0x00FF, 0x1204]);
+        assert_eq!(detection.platform, Platform::Chip8);
+        assert!(detection.reasons[0].starts_with("Ignoring"));
+
+        let detection = detect_program(&[0xB204, 0x00FF]);
+        assert_eq!(detection.platform, Platform::SuperChip);
+    }
+
+    #[test]
+    fn shifts_decide_the_shift_quirk() {
+        // SUPER-CHIP shifts in place, but these shift V1 into V0
+        let detection = detect_program(&[0x00FF, 0x8016, 0x801E, 0x1206]);
+        assert_eq!(detection.platform, Platform::SuperChip);
+        assert!(!detection.quirks.shifting);
+
+        let detection = detect_program(&[0x8106, 0x1202]);
+        assert!(detection.quirks.shifting);
+    }
+
+    #[test]
+    fn reusing_i_after_a_load_sets_the_increment_quirk() {
+        let detection = detect_program(&[0x00FF, 0xA300, 0xF165, 0xF165, 0x1208]);
+        assert_eq!(detection.platform, Platform::SuperChip);
+        assert!(detection.quirks.memory_increment);
+
+        // setting I again in between doesn't rely on the increment
+        let detection = detect_program(&[0x00FF, 0xA300, 0xF165, 0xA302, 0xF165, 0x120A]);
+        assert!(!detection.quirks.memory_increment);
+    }
+}
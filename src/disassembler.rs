@@ -0,0 +1,81 @@
+use crate::extensions;
+
+/// Turns a single opcode into its assembly mnemonic, opcodes that don't decode
+/// into a known instruction or a registered extension are shown as raw data words
+pub fn disassemble(opcode: u16) -> String {
+    if let Some(extension) = extensions::find(opcode) {
+        return extension.mnemonic(opcode);
+    }
+
+    let x = (opcode & 0xF00) >> 8;
+    let y = (opcode & 0xF0) >> 4;
+    let n = opcode & 0xF;
+    let kk = opcode & 0xFF;
+    let nnn = opcode & 0xFFF;
+
+    match opcode >> 12 {
+        0 => match opcode {
+            0x00E0 => "CLS".to_owned(),
+            0x00EE => "RET".to_owned(),
+            _ => format!("SYS 0x{:03X}", nnn),
+        },
+        1 => format!("JP 0x{:03X}", nnn),
+        2 => format!("CALL 0x{:03X}", nnn),
+        3 => format!("SE V{:X}, 0x{:02X}", x, kk),
+        4 => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        5 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        6 => format!("LD V{:X}, 0x{:02X}", x, kk),
+        7 => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        8 => match n {
+            0 => format!("LD V{:X}, V{:X}", x, y),
+            1 => format!("OR V{:X}, V{:X}", x, y),
+            2 => format!("AND V{:X}, V{:X}", x, y),
+            3 => format!("XOR V{:X}, V{:X}", x, y),
+            4 => format!("ADD V{:X}, V{:X}", x, y),
+            5 => format!("SUB V{:X}, V{:X}", x, y),
+            6 => format!("SHR V{:X}", x),
+            7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}", x),
+            _ => data_word(opcode),
+        },
+        9 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, 0x{:03X}", nnn),
+        0xB => format!("JP V0, 0x{:03X}", nnn),
+        0xC => format!("RND V{:X}, 0x{:02X}", x, kk),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => data_word(opcode),
+        },
+        0xF => match kk {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => data_word(opcode),
+        },
+        _ => data_word(opcode),
+    }
+}
+
+/// Returns true if the opcode belongs to the instruction set the emulator implements itself
+pub fn is_builtin(opcode: u16) -> bool {
+    match opcode >> 12 {
+        5 | 9 => opcode & 0xF == 0,
+        8 => matches!(opcode & 0xF, 0x0..=0x7 | 0xE),
+        0xE => matches!(opcode & 0xFF, 0x9E | 0xA1),
+        0xF => matches!(opcode & 0xFF, 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x55 | 0x65),
+        _ => true,
+    }
+}
+
+fn data_word(opcode: u16) -> String {
+    format!("DW 0x{:04X}", opcode)
+}
@@ -0,0 +1,86 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::postprocess::PostProcessing;
+
+/// Display settings are kept here between runs
+pub const SETTINGS_FILE: &str = "display.json";
+/// Width and height of the CHIP-8 screen
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 32;
+
+/// How the screen is fit into the space it's shown in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ScaleMode {
+    /// Largest whole multiple of the screen size that fits, the rest is letterboxed
+    Integer,
+    /// As large as possible while keeping the 2:1 aspect ratio
+    Aspect,
+    /// Fills all of the space
+    Stretch,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 3] = [ScaleMode::Integer, ScaleMode::Aspect, ScaleMode::Stretch];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::Integer => "Integer scaling",
+            ScaleMode::Aspect => "Keep aspect ratio",
+            ScaleMode::Stretch => "Stretch",
+        }
+    }
+
+    /// Area the screen gets drawn to inside of a space of the given size, as x, y, width and height
+    pub fn fit(&self, width: f32, height: f32) -> [f32; 4] {
+        let scale = (width / WIDTH as f32).min(height / HEIGHT as f32);
+        let (screen_width, screen_height) = match self {
+            ScaleMode::Integer => {
+                let scale = scale.floor().max(1f32);
+                (WIDTH as f32 * scale, HEIGHT as f32 * scale)
+            },
+            ScaleMode::Aspect => (WIDTH as f32 * scale, HEIGHT as f32 * scale),
+            ScaleMode::Stretch => (width, height),
+        };
+        [((width - screen_width) / 2f32).floor(), ((height - screen_height) / 2f32).floor(), screen_width, screen_height]
+    }
+}
+
+/// How and where the screen is shown, saved whenever the emulator stops or the ui closes
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DisplaySettings {
+    pub scale_mode: ScaleMode,
+    /// The SDL window covers the whole monitor, the display window fills the whole ui
+    pub fullscreen: bool,
+    /// Size of the SDL window in pixels
+    pub sdl_window_size: [u32; 2],
+    /// Size of the ui window in points
+    pub ui_window_size: [f32; 2],
+    #[serde(default)]
+    pub post_processing: PostProcessing,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self { scale_mode: ScaleMode::Integer, fullscreen: false, sdl_window_size: [640, 320], ui_window_size: [1024f32, 720f32], post_processing: PostProcessing::default() }
+    }
+}
+
+impl DisplaySettings {
+    /// Loads the settings of the last run, the defaults if there are none
+    pub fn load() -> Self {
+        match fs::read_to_string(SETTINGS_FILE) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("{}: {}", SETTINGS_FILE, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(SETTINGS_FILE, json).map_err(|e| format!("{}: {}", SETTINGS_FILE, e))
+    }
+}
@@ -1,50 +1,643 @@
 #![allow(arithmetic_overflow)]
 
 
-use std::io::{Read};
 use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 use std::{thread, usize};
-use std::fs::File;
+use std::fs;
+use std::ops::Range;
+use std::time::Instant;
 
 use rand::Rng;
 
 use egui::mutex::{Mutex, MutexGuard};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::event::WindowEvent;
+use sdl2::rect::Rect;
+use sdl2::video::FullscreenType;
 use sdl2::{Sdl, render::Canvas, video::Window};
 use sdl2::render::{RenderTarget};
 
+use crate::analyzer::Analysis;
+use crate::capture;
+use crate::coverage::Coverage;
+use crate::detector;
+use crate::database::Database;
+use crate::display::DisplaySettings;
+use crate::extensions;
+use crate::fonts::{self, Font};
+use crate::machine::MachineConfig;
+use crate::machine_code::{self, MachineCode};
+use crate::palette::PaletteSet;
+use crate::postprocess::{self, Frame, PostProcessor};
+use crate::profiler::Profiler;
+use crate::recording::Recorder;
+use crate::quirks::Quirks;
+use crate::scheduler::Scheduler;
+use crate::vip::Vip;
+use crate::vip_timing::{Operands, VipClock};
 use crate::emulator_ui::InterThreadData;
 
 const WINDOW_TITLE: &str = "CHIP-8";
+/// Runs the emulator as fast as possible while held down
+const FAST_FORWARD_KEY: Keycode = Keycode::Tab;
+/// Toggles slow motion
+const SLOW_MOTION_KEY: Keycode = Keycode::F3;
+/// Runs a single frame while frozen
+const FRAME_ADVANCE_KEY: Keycode = Keycode::F4;
+/// Toggles fullscreen
+const FULLSCREEN_KEY: Keycode = Keycode::F11;
+/// Saves a screenshot
+const SCREENSHOT_KEY: Keycode = Keycode::F12;
+/// Starts and stops recording
+const RECORD_KEY: Keycode = Keycode::F9;
+
+/// Address programs get loaded at
+pub const PROGRAM_START: usize = 0x200;
+/// Timer and screen updates per second
+pub const TIMER_FREQUENCY: u32 = 60;
+/// Instructions executed every frame unless the user or the rom database picks another speed
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 8;
+/// Frames of sprite draws kept around for the display overlay
+pub const DRAW_HISTORY_FRAMES: usize = 30;
+
+pub const FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+/// Key pressed or released on the host, keycodes are SDL keycodes whether the key came from the SDL window or the ui
+#[derive(Clone, Copy, Debug)]
+pub struct KeyEvent {
+    pub keycode: Keycode,
+    pub pressed: bool,
+    /// Sent again because the key is held down
+    pub repeat: bool,
+}
 
 struct GraphicsContext<T: RenderTarget>{
     sdl_ctx: Sdl,
     canvas: Canvas<T>,
 }
 
+/// Progress of an FX0A waiting for a key
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyWait {
+    /// Waiting for any key to be pressed, the key gets stored into the register
+    Press { register: usize },
+    /// The key was pressed, waiting for it to be released before it gets stored
+    Release { register: usize, key: u8 },
+}
+
+impl KeyWait {
+    pub fn description(&self) -> String {
+        match self {
+            KeyWait::Press { register } => format!("Waiting for a key press into V{:X}", register),
+            KeyWait::Release { register, key } => format!("Waiting for key {:X} to be released into V{:X}", key, register),
+        }
+    }
+}
+
+/// Last DXYN that toggled a pixel of the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DrawRecord {
+    /// Address of the DXYN
+    pub pc: u16,
+    /// Address of the sprite data it drew
+    pub i: u16,
+    /// Instructions executed before it
+    pub cycle: u64,
+}
+
+/// Area of the screen a DXYN drew to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SpriteDraw {
+    pub x: u8,
+    pub y: u8,
+    pub height: u8,
+    /// The draw erased a pixel and set VF to 1
+    pub collision: bool,
+}
+
 #[allow(non_snake_case, dead_code)]
 #[derive(Clone)]
 pub struct C8 {
-    pub memory: [u8; 4096],
+    pub memory: Vec<u8>,
     pub V: [u8; 16],
     pub I: u16,
     pub PC: u16,
-    pub stack: [u16; 16],
+    pub stack: Vec<u16>,
     pub SP: usize,
     pub delay_timer: u8,
     pub sound_timer: u8,
+    pub quirks: Quirks,
+    /// Layout memory and the stack were set up with
+    pub config: MachineConfig,
+    /// FX29 points I at the font loaded here, FX30 at the large font right behind it
+    pub font_address: u16,
+    /// Routines 0NNN calls into
+    pub machine_code: MachineCode,
+    /// Set when an instruction couldn't be executed, the emulator freezes until it's cleared
+    pub fault: Option<String>,
+    /// Set by FX0A, no instructions are executed until the wait completes
+    pub key_wait: Option<KeyWait>,
+    /// Instructions executed since the program started
+    pub cycles: u64,
+    /// Draw that last toggled every pixel of the screen, for the draw inspector
+    pub draw_records: Vec<Option<DrawRecord>>,
+    /// DXYNs executed during the current frame
+    pub sprite_draws: Vec<SpriteDraw>,
     endloop: bool
 }
 
 impl Default for C8{
     fn default() -> Self {
-        Self { memory: [0; 4096], V: [0; 16], I: 0, PC: 0x200, stack: [0; 16], SP: 0, delay_timer: 0, sound_timer: 0, endloop: false }
+        C8::new(MachineConfig::default())
     }
 }
+
+impl C8 {
+    pub fn new(config: MachineConfig) -> Self {
+        Self {
+            memory: vec![config.fill; config.memory_len()],
+            V: [0; 16],
+            I: 0,
+            PC: config.program_start,
+            stack: vec![0; config.stack_depth],
+            SP: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks: Quirks::default(),
+            config,
+            font_address: fonts::DEFAULT_FONT_ADDRESS,
+            machine_code: MachineCode::default(),
+            fault: None,
+            key_wait: None,
+            cycles: 0,
+            draw_records: vec![None; 64*32],
+            sprite_draws: vec![],
+            endloop: false,
+        }
+    }
+
+    /// Loads the program stored in the target file into memory, returns where in memory it ended up.
+    /// The part of the program that doesn't fit into memory is left out.
+    pub fn load_program(&mut self, target_file: &str) -> std::io::Result<Range<usize>> {
+        let program = fs::read(target_file)?;
+        let program_start = (self.config.program_start as usize).min(self.memory.len());
+        let program_len = program.len().min(self.memory.len() - program_start);
+        self.memory[program_start..program_start + program_len].clone_from_slice(&program[..program_len]);
+        Ok(program_start..program_start + program_len)
+    }
+
+    /// Copies the font into memory at the font address, the parts that don't fit into memory are left out
+    pub fn load_font(&mut self, font: &Font) {
+        let start = (self.font_address as usize).min(self.memory.len());
+        let small_len = font.small.len().min(self.memory.len() - start);
+        self.memory[start..start + small_len].clone_from_slice(&font.small[..small_len]);
+        let large_start = start + small_len;
+        let large_len = font.large.len().min(self.memory.len() - large_start);
+        self.memory[large_start..large_start + large_len].clone_from_slice(&font.large[..large_len]);
+    }
+
+    /// Feeds a pressed key to a pending FX0A, which completes right away unless it waits for the release
+    pub fn key_pressed(&mut self, key: u8) {
+        if let Some(KeyWait::Press { register }) = self.key_wait {
+            if self.quirks.key_release {
+                self.key_wait = Some(KeyWait::Release { register, key });
+            }else{
+                self.V[register] = key;
+                self.key_wait = None;
+            }
+        }
+    }
+
+    /// Completes a pending FX0A waiting for a release once its key isn't held down anymore
+    pub fn poll_key_release(&mut self, key_states: &[bool; 16]) {
+        if let Some(KeyWait::Release { register, key }) = self.key_wait {
+            if !key_states[key as usize] {
+                self.V[register] = key;
+                self.key_wait = None;
+            }
+        }
+    }
+
+    /// Reads the opcode stored at the current program counter, memory past the end reads as 0
+    pub fn fetch(&self) -> u16 {
+        let byte = |address: usize| self.memory.get(address).copied().unwrap_or(0) as u16;
+        byte(self.PC as usize) << 8 | byte(self.PC as usize + 1)
+    }
+
+    /// Sets a fault if the program counter doesn't point at a whole instruction inside of memory, returns true if it doesn't
+    pub fn pc_out_of_memory(&mut self) -> bool {
+        if (self.PC as usize) + 1 < self.memory.len() {
+            return false;
+        }
+        self.fault = Some(format!("Program counter left {} memory at 0x{:03X}", self.config.memory_size.name(), self.PC));
+        true
+    }
+
+    /// Moves the program counter two bytes ahead, sets a fault instead of wrapping around past 0xFFFF.
+    /// Returns true if it couldn't be moved.
+    pub fn advance_pc(&mut self) -> bool {
+        match self.PC.checked_add(2) {
+            Some(pc) => {
+                self.PC = pc;
+                false
+            },
+            None => {
+                self.fault = Some(format!("Program counter ran past the end of {} memory", self.config.memory_size.name()));
+                true
+            },
+        }
+    }
+
+    /// Points I at the address, sets a fault instead of wrapping around past 0xFFFF
+    fn set_i(&mut self, address: usize) {
+        match u16::try_from(address) {
+            Ok(address) => self.I = address,
+            Err(_) => self.fault = Some(format!("I ran past the end of {} memory at 0x{:X}", self.config.memory_size.name(), address)),
+        }
+    }
+
+    /// Sets a fault if the `len` bytes at the address don't fit into memory, returns true if they don't
+    fn out_of_memory(&mut self, address: u16, len: usize) -> bool {
+        if address as usize + len <= self.memory.len() {
+            return false;
+        }
+        self.fault = Some(format!("Memory access at 0x{:03X}-0x{:03X} is outside of {} memory", address, address as usize + len - 1, self.config.memory_size.name()));
+        true
+    }
+
+    /// Executes a single fetched opcode, the program counter is expected to already point past it.
+    /// Returns a human readable description of what the instruction did.
+    pub fn execute(&mut self, opcode: u16, gbuf: &mut [u8; 64*32], key_states: &[bool; 16]) -> String {
+        let cycle = self.cycles;
+        self.cycles += 1;
+        if let Some(extension) = extensions::find(opcode) {
+            return extension.execute(opcode, self, gbuf);
+        }
+
+        let mut opcode_description = "Unknown/unimplemented instruction".to_owned();
+
+        if self.out_of_memory(self.PC.wrapping_sub(2), 2) {
+            return format!("Program counter left memory at 0x{:03X}", self.PC.wrapping_sub(2));
+        }
+
+        match opcode >> 12 {
+            0 => {
+                match opcode & 0xFF {
+                    0xE0 => { // 0x00E0 - clear the screen
+                        opcode_description = "Clearing screen".to_owned();
+                        gbuf.clone_from(&[0; 64*32]);
+                    },
+                    0xEE if self.SP == 0 => {
+                        opcode_description = "Returning from subroutine with an empty stack".to_owned();
+                        self.fault = Some("Stack underflow".to_owned());
+                    },
+                    0xEE => { // 0x00EE - return from subroutine call
+                        opcode_description = format!("Reuturning from subroutine to: 0x{:03X}", self.stack[self.SP - 1]);
+                        self.SP -= 1;
+                        self.PC = self.stack[self.SP];
+                    },
+                    _ => { // 0x0NNN - call machine code routine at NNN
+                        let nnn = opcode & 0xFFF;
+                        if let Some((name, routine)) = self.machine_code.get(nnn).cloned() {
+                            opcode_description = format!("Calling machine code routine at 0x{:03X}: {}", nnn, name);
+                            routine(self, gbuf);
+                        }else if self.machine_code.trap_unhandled {
+                            opcode_description = format!("Unhandled machine code routine at 0x{:03X}", nnn);
+                            self.fault = Some(opcode_description.clone());
+                        }else{
+                            opcode_description = format!("Ignoring machine code routine at 0x{:03X}", nnn);
+                        }
+                    }
+                }
+            },
+            1 => { // 0x1NNN - jump to location NNN
+                let nnn = opcode & 0xFFF;
+                if self.PC.wrapping_sub(2) == nnn {
+                    opcode_description = "Endloop".to_owned();
+                    self.endloop = true;
+                }else{
+                    opcode_description = format!("Jumping to location 0x{:03X}", nnn);
+                }
+
+                self.PC = nnn;
+            },
+            2 => { // 0x2NNN - jump to subroutine at address NNN
+                let nnn = opcode & 0xFFF;
+                opcode_description = format!("Jumping to subroutine at 0x{:03X}", nnn);
+                if self.SP == self.stack.len() {
+                    self.fault = Some(format!("Stack overflow, more than {} nested subroutine calls", self.stack.len()));
+                    return opcode_description;
+                }
+                self.stack[self.SP] = self.PC;
+                self.SP += 1;
+                self.PC = nnn;
+            },
+            3 => { // 0x3XRR - skip next instruction if V[X] == 0xRR 
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let rr = (opcode & 0xFF) as u8;
+                opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) == 0x{:02X}",x,self.V[x as usize], rr);
+                if self.V[x] == rr {
+                    self.advance_pc();
+                }
+            },
+            4 => { // 0x4XRR - skip next intruction if V[X] != 0xRR
+                let x = (opcode & 0xF00) >> 8;
+                let rr = (opcode & 0xFF) as u8;
+                opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) != 0x{:02X}",x,self.V[x as usize], rr);
+                if self.V[x as usize] != rr {
+                    self.advance_pc();
+                }
+            },
+            5 => { // 0x5XY0 - skip next instruction if V[X] == V[Y]
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let y = ((opcode & 0xF0) >> 4) as usize;
+                opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) == V{:X}(0x{:02X})", x, self.V[x], y, self.V[y]);
+                if self.V[x] == self.V[y] {
+                    self.advance_pc();
+                }
+            },
+            6 => { // 0x6XRR - move constant RR into V[X]
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let rr = (opcode & 0xFF) as u8;
+                opcode_description = format!("Moving 0x{:02X} into V{:X}", rr, x);
+                self.V[x] = rr;
+            },
+            7 => { // 0x7XRR - add RR to value of V[X]
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let rr = (opcode & 0xFF) as u8;
+                opcode_description = format!("Adding 0x{:02X} to V{:X}", rr, x);
+                self.V[x] = self.V[x].wrapping_add(rr);
+            },
+            8 => {
+                match opcode & 0xF {
+                    0 => { // 0x8XY0 - move register VY to register VX
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Moving V{:X} into V{:X}", y, x);
+                        self.V[x] = self.V[y];
+                    }
+                    1 => { // 0x8XY1 - stores the value of VX | VY into VX
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Adding V{:X}to V{:X} OR V{:X})",x,x,y);
+                        self.V[x] |= self.V[y];
+                        if self.quirks.vf_reset {
+                            self.V[0xF] = 0;
+                        }
+                    },
+                    2 => { // 0x8XY2 - add value of VY to VX
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Set V{:X} to V{:X} AND V{:X}", x, x, y);
+                        self.V[x] &= self.V[y];
+                        if self.quirks.vf_reset {
+                            self.V[0xF] = 0;
+                        }
+                    },
+                    3 => { // 0x8XY3 - XOR VY and X store in VX
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Set V{:X} to V{:X} XOR V{:X}", x, x, y);
+                        self.V[x] ^= self.V[y];
+                        if self.quirks.vf_reset {
+                            self.V[0xF] = 0;
+                        }
+                    },
+                    4 => { // 0x8XY4 - Add VY to VX store carry in V15
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Add V{:X} to V{:X} and store carry in VF", y, x);
+                        self.V[0xF] = if self.V[x] as i32 + self.V[y] as i32 > 255 {1} else {0};
+                        self.V[x] = self.V[x].wrapping_add(self.V[y]);
+                    },
+                    5 => { // 0x8XY5 - Subtract VY from VX and store the borrow in V15
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Subtract V{:X} from V{:X} and store the borrow in VF" ,y ,x);
+                        self.V[0xF] = if self.V[x] > self.V[y] {1} else {0};
+                        self.V[x] = self.V[x].wrapping_sub(self.V[y]);
+                    },
+                    6 => { // 0x8XY6 - Shift VX (or VY without the shifting quirk) to right, first bit goes to V[15]
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = if self.quirks.shifting {x} else {((opcode & 0xF0) >> 4) as usize};
+                        opcode_description = format!("Shift V{:X} to the right into V{:X} least significant bit goes to VF", y, x);
+                        let source = self.V[y];
+                        self.V[0xF] = source & 1;
+                        self.V[x] = source >> 1;
+                    },
+                    7 => { // 0x8XY7 - Subtract VX from VY result stored in VX and store the borrow in V15
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = ((opcode & 0xF0) >> 4) as usize;
+                        opcode_description = format!("Subtract V{:X} from V{:X} store the result to V{:X} and store the borrow in VF" ,x ,y, x);
+                        self.V[0xF] = if self.V[y] > self.V[x] {1} else {0};
+                        self.V[x] = self.V[y].wrapping_sub(self.V[x]);
+                    },
+                    0xE => { // 0x8XYE - Shift VX (or VY without the shifting quirk) to left,most significant bit goes to V15
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        let y = if self.quirks.shifting {x} else {((opcode & 0xF0) >> 4) as usize};
+                        opcode_description = format!("Shift V{:X} to the left into V{:X} most significant bit goes to VF", y, x);
+                        let source = self.V[y];
+                        self.V[0xF] = source >> 7;
+                        self.V[x] = source << 1;
+                    },
+                    _ => {}
+                }
+            },
+            0x9 => { // 0x9XYN - Skip next instruction if Vx != VY
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let y = ((opcode & 0xF0) >> 4) as usize;
+                opcode_description = format!("Skipping next instruction if V{:X} != V{:X}", x, y);
+                if self.V[x] != self.V[y] {
+                    self.advance_pc();
+                }
+            },
+            0xA => { // 0xANNN - Put NNN into I
+                let nnn = opcode & 0xFFF;
+                opcode_description = format!("Put 0x{:03X} into I", nnn);
+                self.I = nnn;
+            },
+            0xB => {  // 0xBNNN - Jump to address NNN plus register V0 (VX with the jumping quirk)
+                let nnn = opcode & 0xFFF;
+                let x = if self.quirks.jumping {((opcode & 0xF00) >> 8) as usize} else {0};
+                opcode_description = format!("Jump to V{:X} + 0x{:03X}", x, nnn);
+                self.PC = nnn + self.V[x] as u16;
+            },
+            0xC => { // 0xCXKK - Set VX to (random number between 0 - 255) & KK
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let kk= (opcode & 0xFF) as u8;
+                let rnd = rand::thread_rng().gen_range(0..=255) as u8;
+                opcode_description = format!("Set V{:X} to random number in [0,255] & 0x{:02X}", x, kk);
+                self.V[x] = rnd & kk;
+            },  
+            /*
+            *
+            *	Dxyn - DRW Vx, Vy, nibble
+            *	Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+            *	The interpreter reads n bytes from memory, starting at the address stored in I. These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). Sprites are XORed onto the existing screen.
+            *	If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside the coordinates of the display, 
+            *	it wraps around to the opposite side of the screen.
+            *	
+            *	A sprite is 8 bits of length and n bits of height
+            *
+            */
+            0xD => {
+                let x = ((opcode & 0xF00) >> 8) as usize;
+                let y = ((opcode & 0xF0) >> 4) as usize;
+                let n = opcode & 0xF;
+                let sx = self.V[x] as usize;
+                let sy = self.V[y] as usize;
+
+                opcode_description = format!("Draw sprite at {}, {} with length {}", sx,sy,n);
+
+                // with clipping only the starting position wraps around, pixels past the edges are dropped
+                let (sx, sy) = if self.quirks.clipping {(sx % 64, sy % 32)} else {(sx, sy)};
+
+                if self.out_of_memory(self.I, n as usize) {
+                    return opcode_description;
+                }
+
+                self.V[0xF] = 0;
+                let record = DrawRecord { pc: self.PC.wrapping_sub(2), i: self.I, cycle };
+
+                for i in 0..n as usize {
+                    let pixel = self.memory[self.I as usize + i as usize];
+                    for j in 0..8usize {
+                        if self.quirks.clipping && (j + sx >= 64 || i + sy >= 32) {
+                            continue;
+                        }
+                        if pixel & (0b10000000 >> j) > 0 {
+                            let index = (j+sx)%64 + ((i+sy)%32)*64;
+                            self.V[0xF] = self.V[0xF].max(gbuf[index]);
+                            gbuf[index] ^= 1;
+                            self.draw_records[index] = Some(record);
+                        }
+                    }
+                }
+                self.sprite_draws.push(SpriteDraw { x: (sx % 64) as u8, y: (sy % 32) as u8, height: n as u8, collision: self.V[0xF] == 1 });
+            },
+            0xE => {
+                match opcode & 0xFF {
+                    0x9E => { // 0xEx9E - skip next instruction if key in Vx is pressed
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Skipping next instruction if key in V{:X} ({:X}) is pressed", x, self.V[x]);
+                        // only the low nibble names a key
+                        if key_states[(self.V[x] & 0xF) as usize] {
+                            self.advance_pc();
+                        }
+                    },
+                    0xA1 => { // 0xEx9E - skip next instruction if key in Vx is pressed
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Skipping next instruction if key in V{:X} ({:X}) is not pressed", x, self.V[x]);
+                        if !key_states[(self.V[x] & 0xF) as usize] {
+                            self.advance_pc();
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            0xF => {
+                match opcode & 0xFF { // 0xFx07 - put delay timer into Vx
+                    0x7 => {
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Putting value of delay timer into V{:X}",x);
+                        self.V[x] = self.delay_timer;
+                    },
+                    0xA => { // 0xFx0A - Wait for key press store the value of the key in Vx
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Waiting for keypress and storing result into V{:X}", x);
+                        self.key_wait = Some(KeyWait::Press { register: x });
+                    },
+                    0x15 => { // 0xFx15 - Set delay timer to value of Vx
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Setting delay timer to the value of V{:X}", x);
+                        self.delay_timer = self.V[x];
+                    },
+                    0x18 => { // 0xFx18 - set sound timer value to Vx
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Setting sound timer to the value of V{:X}", x);
+                        self.sound_timer = self.V[x];
+                    },
+                    0x1E => { // 0xFx1E - value of Vx is added to I
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Adding the value of V{:X} to I", x);
+                        self.set_i(self.I as usize + self.V[x] as usize);
+                    },
+                    0x29 => { // 0xFx29 - the value of I is set to sprite location of digit Vx
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Setting I to location of the sprite of the digit {:X}", x);
+                        self.set_i(self.font_address as usize + self.V[x] as usize * fonts::SMALL_GLYPH_HEIGHT);
+                    },
+                    0x30 => { // 0xFx30 - the value of I is set to the large sprite location of digit Vx
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Setting I to location of the large sprite of the digit {:X}", x);
+                        self.set_i(self.font_address as usize + 16 * fonts::SMALL_GLYPH_HEIGHT + self.V[x] as usize * fonts::LARGE_GLYPH_HEIGHT);
+                    },
+                    0x33 => { // 0xFx33 - store BCD represebtation of Vx in I
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Storing BCD representation of V{:X} into location I", x);
+                        if self.out_of_memory(self.I, 3) {
+                            return opcode_description;
+                        }
+                        self.memory[self.I as usize] = self.V[x] / 100;
+                        self.memory[self.I as usize + 1] = (self.V[x] / 10) % 10;
+                        self.memory[self.I as usize + 2] = self.V[x] % 10;
+                    },
+                    0x55 => { // 0xFx55 - store the value of registers 0 to X into memory at I
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Storing values of register [0, {:X}] into memory at I", x);
+                        if self.out_of_memory(self.I, x + 1) {
+                            return opcode_description;
+                        }
+                        let mem_slice = &mut self.memory[self.I as usize..=self.I as usize + x];
+                        let v_slice = &self.V[0..=x];
+                        mem_slice.clone_from_slice(v_slice);
+                        if self.quirks.memory_increment {
+                            self.set_i(self.I as usize + x + 1);
+                        }
+                    },
+                    0x65 => { // 0xFx65 load registers from V0 to VX from location I
+                        let x = ((opcode & 0xF00) >> 8) as usize;
+                        opcode_description = format!("Loading values of register [0, {:X}] from address I", x);
+                        if self.out_of_memory(self.I, x + 1) {
+                            return opcode_description;
+                        }
+                        let v_slice = &mut self.V[0..=x];
+                        let mem_slice = &self.memory[self.I as usize..=self.I as usize + x];
+                        v_slice.clone_from_slice(mem_slice);
+                        if self.quirks.memory_increment {
+                            self.set_i(self.I as usize + x + 1);
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+
+        opcode_description
+    }
+}
+
 //#[allow(dead_code)]
 struct UIInterface{
     kill_receiver: Receiver<bool>,
@@ -71,24 +664,35 @@ impl UIInterface{
 
 pub struct Emulator{
     ui_interface: UIInterface,
-    context: GraphicsContext<Window>,
+    /// Standalone SDL window, only opened when asked for since the ui shows the display as well
+    context: Option<GraphicsContext<Window>>,
     keymap: [i32; 16],
+    /// Title currently shown by the window, used to only update it when the speed changes
+    window_title: String,
 }
 
 
 impl Emulator{
-    fn init_context() -> GraphicsContext<Window> {
+    /// Opens the SDL window, fullscreen gets turned off in the settings if the window can't go fullscreen
+    fn init_context(settings: &mut DisplaySettings) -> GraphicsContext<Window> {
         let sdl_ctx = sdl2::init().unwrap();
         let video_subsystem = sdl_ctx.video().unwrap();
 
-        let window = video_subsystem
-            .window(WINDOW_TITLE, 640, 420)
+        let [width, height] = settings.sdl_window_size;
+        let mut window = video_subsystem
+            .window(WINDOW_TITLE, width, height)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
+        if settings.fullscreen {
+            if let Err(e) = window.set_fullscreen(FullscreenType::Desktop) {
+                eprintln!("Couldn't go fullscreen: {}", e);
+                settings.fullscreen = false;
+            }
+        }
         
-        let mut canvas = window.into_canvas().build().unwrap();
-        canvas.set_logical_size(64, 32).unwrap();
+        let canvas = window.into_canvas().build().unwrap();
         
 
         GraphicsContext{ sdl_ctx: sdl_ctx, canvas: canvas }
@@ -97,10 +701,15 @@ impl Emulator{
     fn new(kill_receiver: Receiver<bool>, target_file: String, egui_ctx: egui::Context, inter_thread: Arc<Mutex<InterThreadData>>) -> Emulator {
         inter_thread.lock().executed_instructions.clear();
         inter_thread.lock().internal_state.clone_from(&C8::default());
+        let context = {
+            let mut locked = inter_thread.lock();
+            if locked.sdl_window {Some(Emulator::init_context(&mut locked.display_settings))} else {None}
+        };
         Emulator { 
             ui_interface: UIInterface::new(kill_receiver, target_file, egui_ctx, inter_thread),
-            context: Emulator::init_context(),
+            context,
             keymap: [0; 16],
+            window_title: WINDOW_TITLE.to_owned(),
         }
     }
     
@@ -112,7 +721,6 @@ impl Emulator{
         if locked.executed_instructions.len() > 100 {
             locked.executed_instructions.remove(0);
         }
-        locked.internal_state.clone_from(internal_state);
     }
 
     fn keycode_to_index(keycode: usize, keymap: &[i32; 16]) -> Option<usize>{
@@ -125,431 +733,520 @@ impl Emulator{
     }
 
     fn start(&mut self){
-        let timer = self.context.sdl_ctx.timer().unwrap();
-        let mut current_tick: u32;
-
         {
             self.keymap.clone_from(&self.ui_interface.inter_thread.lock().keymap);
         }
 
-        macro_rules! clocked {
-            ($code:block, $last_tick:expr, $freq:expr) => {
-                if current_tick - $last_tick >= 1000/$freq {
-                    $code;
-                    $last_tick = current_tick;
-                }
-            };
-            ($code:expr, $last_tick:expr, $freq:expr) => {
-                if current_tick - $last_tick >= 1000/$freq {
-                    $code();
-                    $last_tick = current_tick;
+        // the program runs on the original interpreter instead when a COSMAC VIP is emulated,
+        // the emulator doesn't start if its images can't be loaded
+        let mut vip = {
+            let mut locked = self.ui_interface.inter_thread.lock();
+            locked.vip_status.clear();
+            if locked.cosmac_vip {
+                let monitor = Some(locked.vip_monitor_path.as_str()).filter(|path| !path.is_empty());
+                match Vip::new(&locked.vip_interpreter_path, monitor, &self.ui_interface.target_file) {
+                    Ok(vip) => Some(vip),
+                    Err(e) => {
+                        locked.vip_status = format!("COSMAC VIP couldn't be started: {}", e);
+                        return;
+                    },
                 }
-            };
-        }
+            }else{
+                None
+            }
+        };
 
-        let mut event_pump = self.context.sdl_ctx.event_pump().unwrap();
-        let mut internals = C8::default();
+        let mut event_pump = self.context.as_ref().map(|context| context.sdl_ctx.event_pump().unwrap());
+        let mut internals = C8::new(self.ui_interface.inter_thread.lock().machine_config);
 
+        let program = internals.load_program(&self.ui_interface.target_file).unwrap();
+        let (program_start, program_len) = (program.start, program.len());
         {
-            let mut file = File::open(self.ui_interface.target_file.clone()).unwrap();
+            let mut locked = self.ui_interface.inter_thread.lock();
+            locked.coverage = Coverage::new(program_start, program_len, internals.memory.len());
+            locked.profiler = Profiler::new(program_start as u16);
+            locked.unhandled_machine_code.clear();
+            locked.analysis = Analysis::analyze(&internals.memory, program_start, program_len);
+            let detection = detector::detect(&internals.memory, &locked.analysis);
+            if locked.apply_detection {
+                locked.platform = detection.platform;
+                locked.quirks = detection.quirks;
+            }
+            locked.detection = Some(detection);
 
-            file.read(&mut internals.memory[0x200..]).unwrap();
+            let rom_entry = locked.database.lookup(&internals.memory[program.clone()]);
+            if let (Some(entry), true) = (&rom_entry, locked.apply_database) {
+                locked.platform = entry.platform;
+                locked.quirks = entry.quirks;
+                if let Some(tickrate) = entry.tickrate {
+                    locked.instructions_per_frame = tickrate;
+                }
+                if let Some(palette) = entry.colors {
+                    locked.palette = palette;
+                    locked.palette_set = PaletteSet::Custom;
+                }
+                entry.apply_keys(&mut self.keymap);
+            }
+            locked.rom_entry = rom_entry;
+            // a palette the user picked for the rom wins over the database's
+            locked.rom_hash = Database::hash(&internals.memory[program]);
+            if let Some(palette) = locked.palette_overrides.get(&locked.rom_hash) {
+                locked.palette = palette;
+                locked.palette_set = PaletteSet::Custom;
+            }
+            locked.key_events.clear();
+            locked.memory_writes.clear();
+            locked.recent_draws.clear();
+
+            internals.font_address = locked.font_address;
+            internals.load_font(&locked.font);
+            locked.font_changed = false;
         }
 
+
         let mut gbuf = [0u8; 64*32];
-        
-        let fontset: [u8; 80] = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-        internals.memory[0..80].clone_from_slice(&fontset);
-
-        let mut last_opcode_tick = 0u32;
-        let mut last_render_tick = 0u32;
+        let mut post_processor = PostProcessor::default();
+        // recordings get every frame that runs, so they are post-processed on their own
+        let mut recorder: Option<(Recorder, PostProcessor)> = None;
+
+        let mut scheduler = Scheduler::new(TIMER_FREQUENCY as f32);
+        let mut vip_clock = VipClock::default();
+        let mut last_render = Instant::now();
         let mut frozen = false;
 
+        // speed settings are copied out of the shared data every frame so the loop doesn't need to lock on every iteration
+        let mut frame_frequency = TIMER_FREQUENCY as f32;
+        let mut fast_forward = false;
+        let mut fast_forward_held = false;
+        let mut speed_title = String::new();
+
         let mut key_states = [false; 16];
 
-        let mut wfi_register: i8 = -1; // -1 non blocking, everything else the key gets stored inside
 
         'running: loop {
             if let Ok(_) = self.ui_interface.kill_receiver.try_recv() {
                 break 'running;
             }
 
-            for event in event_pump.poll_iter() {
-                if let Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape | Keycode::Q), .. } = event {
-                    break 'running;
-                } 
-
-                if let Event::KeyDown { keycode: Some(key), .. } = event{
-                    let key = Emulator::keycode_to_index(key as usize, &self.keymap);
-                    match key {
-                        Some(key) => { 
-                            //println!("{}", key as i32);
-                            key_states[key] = true; 
-                            if wfi_register != -1 && !frozen{
-                                internals.V[wfi_register as usize] = key as u8;  
-                                wfi_register = -1;
+            // keys pressed in the ui come in alongside the ones pressed in the SDL window
+            let mut key_events = std::mem::take(&mut self.ui_interface.inter_thread.lock().key_events);
+            if let Some(event_pump) = &mut event_pump {
+                for event in event_pump.poll_iter() {
+                    match event {
+                        Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape | Keycode::Q), .. } => break 'running,
+                        Event::KeyDown { keycode: Some(keycode), repeat, .. } => key_events.push(KeyEvent { keycode, pressed: true, repeat }),
+                        Event::KeyUp { keycode: Some(keycode), repeat, .. } => key_events.push(KeyEvent { keycode, pressed: false, repeat }),
+                        Event::Window { win_event: WindowEvent::Resized(width, height), .. } => {
+                            let locked = &mut self.ui_interface.inter_thread.lock();
+                            if !locked.display_settings.fullscreen {
+                                locked.display_settings.sdl_window_size = [width as u32, height as u32];
                             }
                         },
-                        None => {},
-                    }  
+                        _ => {}
+                    }
                 }
+            }
 
-                if let Event::KeyUp { keycode: Some(key), .. } = event {
-                    let key = Emulator::keycode_to_index(key as usize, &self.keymap);
-                    match key {
-                        Some(key) => { key_states[key] = false; },
-                        None => {},
+            for event in key_events {
+                // <speed hotkeys>
+                match event {
+                    KeyEvent { keycode: FAST_FORWARD_KEY, pressed, .. } => fast_forward_held = pressed,
+                    KeyEvent { keycode: SLOW_MOTION_KEY, pressed: true, repeat: false } => {
+                        let locked = &mut self.ui_interface.inter_thread.lock();
+                        locked.slow_motion = !locked.slow_motion;
+                    },
+                    KeyEvent { keycode: FRAME_ADVANCE_KEY, pressed: true, .. } => {
+                        self.ui_interface.inter_thread.lock().frame_advance = true;
+                    },
+                    KeyEvent { keycode: FULLSCREEN_KEY, pressed: true, repeat: false } => {
+                        let locked = &mut self.ui_interface.inter_thread.lock();
+                        locked.display_settings.fullscreen = !locked.display_settings.fullscreen;
+                    },
+                    KeyEvent { keycode: SCREENSHOT_KEY, pressed: true, repeat: false } => {
+                        self.ui_interface.inter_thread.lock().screenshot_requested = true;
+                    },
+                    KeyEvent { keycode: RECORD_KEY, pressed: true, repeat: false } => {
+                        self.ui_interface.inter_thread.lock().recording_requested = true;
+                    },
+                    _ => {}
+                }
+                // </speed hotkeys>
+
+                if let Some(key) = Emulator::keycode_to_index(event.keycode as usize, &self.keymap) {
+                    key_states[key] = event.pressed;
+                    if event.pressed && !frozen {
+                        internals.key_pressed(key as u8);
                     }
                 }
             }
-            current_tick = timer.ticks();
-            
-            let mut execute_opcodes = ||{
+
+            // <recording>
+            {
                 let locked = &mut self.ui_interface.inter_thread.lock();
-                frozen = locked.freeze; // needs to be written to an external variable so timer updates can also be frozen
-                                        // without needing to use locks,
-                if frozen {
-                    return;
+                if locked.recording_requested {
+                    locked.recording_requested = false;
+                    locked.capture_status = match recorder.take() {
+                        Some((recorder, _)) => {
+                            let path = recorder.path().to_owned();
+                            match recorder.finish() {
+                                Ok(frames) => format!("Saved {} frames to {}", frames, path),
+                                Err(e) => format!("Recording failed: {}", e),
+                            }
+                        },
+                        None => {
+                            let path = capture::capture_path(&self.ui_interface.target_file, locked.recording_format.extension());
+                            match Recorder::start(&path, locked.recording_format, locked.capture_scale, locked.record_audio) {
+                                Ok(started) => {
+                                    recorder = Some((started, PostProcessor::default()));
+                                    format!("Recording to {}", path)
+                                },
+                                Err(e) => format!("Recording failed: {}", e),
+                            }
+                        },
+                    };
                 }
+                locked.recording = recorder.is_some();
+            }
+            // </recording>
 
-                if wfi_register != -1 {
-                    return;
-                }
+            let now = Instant::now();
+            let unthrottled = fast_forward || fast_forward_held;
+            scheduler.set_frequency(frame_frequency);
+            let frames = if unthrottled {
+                scheduler.skip_to(now);
+                1
+            }else{
+                scheduler.due_frames(now)
+            };
 
-                let opcode: u16 = (internals.memory[internals.PC as usize] as u16) << 8 | internals.memory[(internals.PC + 1) as usize] as u16;
-               
-                let old_pc = internals.PC;
-                internals.PC += 2;
-
-                let mut opcode_description = "Unknown/unimplemented instruction".to_owned();
-
-               
-                match opcode >> 12 {
-                    0 => {
-                        match opcode & 0xFF {
-                            0xE0 => { // 0x00E0 - clear the screen
-                                opcode_description = "Clearing screen".to_owned();
-                                gbuf.clone_from(&[0; 64*32]);
-                            },
-                            0xEE => { // 0x00EE - return from subroutine call
-                                opcode_description = format!("Reuturning from subroutine to: 0x{:03X}", internals.stack[internals.SP - 1]);
-                                internals.SP -= 1;
-                                internals.PC = internals.stack[internals.SP];
-                            },
-                            _ => {}
+            for _ in 0..frames {
+                let mut execute_frame = ||{
+                    let locked = &mut self.ui_interface.inter_thread.lock();
+                    frozen = locked.freeze; // needs to be written to an external variable so timer updates can also be frozen
+                                            // without needing to use locks,
+                    fast_forward = locked.fast_forward;
+                    frame_frequency = if locked.slow_motion {
+                        (TIMER_FREQUENCY as f32 * locked.slow_motion_factor).max(1f32)
+                    }else{
+                        TIMER_FREQUENCY as f32
+                    };
+                    speed_title = Emulator::speed_title(locked.instructions_per_frame, locked.cosmac_vip, locked.vip_timing, unthrottled, locked.slow_motion, locked.slow_motion_factor);
+
+                    // edits from the font and sprite editors show up even while frozen
+                    if locked.font_changed {
+                        internals.font_address = locked.font_address;
+                        internals.load_font(&locked.font);
+                        locked.font_changed = false;
+                    }
+                    for (address, byte) in locked.memory_writes.drain(..) {
+                        if let Some(vip) = &mut vip {
+                            vip.write_memory(address, byte);
                         }
-                    },
-                    1 => { // 0x1NNN - jump to location NNN
-                        let nnn = opcode & 0xFFF;
-                        if internals.PC - 2 == nnn {
-                            opcode_description = "Endloop".to_owned();
-                            internals.endloop = true;
-                        }else{
-                            opcode_description = format!("Jumping to location 0x{:03X}", nnn);
+                        if let Some(target) = internals.memory.get_mut(address as usize) {
+                            *target = byte;
                         }
-                        
-                        internals.PC = nnn;
-                    },
-                    2 => { // 0x2NNN - jump to subroutine at address NNN
-                        let nnn = opcode & 0xFFF;
-                        opcode_description = format!("Jumping to subroutine at 0x{:03X}", nnn);
-                        internals.stack[internals.SP] = internals.PC;
-                        internals.SP += 1;
-                        internals.PC = nnn;
-                    },
-                    3 => { // 0x3XRR - skip next instruction if V[X] == 0xRR 
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let rr = (opcode & 0xFF) as u8;
-                        opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) == 0x{:02X}",x,internals.V[x as usize], rr);
-                        if internals.V[x] == rr {
-                            internals.PC += 2;
+                    }
+
+                    let mut executed = 0;
+                    if frozen {
+                        // frame advance runs a single frame and leaves the emulator frozen
+                        if !locked.frame_advance {
+                            return None;
                         }
-                    },
-                    4 => { // 0x4XRR - skip next intruction if V[X] != 0xRR
-                        let x = (opcode & 0xF00) >> 8;
-                        let rr = (opcode & 0xFF) as u8;
-                        opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) != 0x{:02X}",x,internals.V[x as usize], rr);
-                        if internals.V[x as usize] != rr {
-                            internals.PC += 2;
+                    }
+                    locked.frame_advance = false;
+                    internals.fault = None;
+                    internals.sprite_draws.clear();
+                    internals.machine_code.clone_from(&locked.machine_code);
+
+                    if let Some(vip) = &mut vip {
+                        executed = vip.run_frame(&key_states);
+                        vip.render(&mut gbuf);
+                        vip.chip8_state(&mut internals);
+                        locked.internal_state.clone_from(&internals);
+                        return Some(executed);
+                    }
+
+                    // with VIP timing the frame lasts as long as its machine cycles, otherwise for a fixed amount of instructions
+                    let vip_timing = locked.vip_timing;
+                    if vip_timing {
+                        vip_clock.start_frame();
+                    }
+                    loop {
+                        if vip_timing && !vip_clock.has_cycles() || !vip_timing && executed >= locked.instructions_per_frame {
+                            break;
                         }
-                    },
-                    5 => { // 0x5XY0 - skip next instruction if V[X] == V[Y]
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let y = ((opcode & 0xF0) >> 4) as usize;
-                        opcode_description = format!("Skipping next instruction if V{:X}(0x{:02X}) == V{:X}(0x{:02X})", x, internals.V[x], y, internals.V[y]);
-                        if internals.V[x] == internals.V[y] {
-                            internals.PC += 2;
+                        internals.poll_key_release(&key_states);
+                        if internals.key_wait.is_some() {
+                            vip_clock.wait();
+                            break;
                         }
-                    },
-                    6 => { // 0x6XRR - move constant RR into V[X]
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let rr = (opcode & 0xFF) as u8;
-                        opcode_description = format!("Moving 0x{:02X} into V{:X}", rr, x);
-                        internals.V[x] = rr;
-                    },
-                    7 => { // 0x7XRR - add RR to value of V[X]
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let rr = (opcode & 0xFF) as u8;
-                        opcode_description = format!("Adding 0x{:02X} to V{:X}", rr, x);
-                        internals.V[x] = internals.V[x].wrapping_add(rr);
-                    },
-                    8 => {
-                        match opcode & 0xF {
-                            0 => { // 0x8XY0 - move register VY to register VX
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Moving V{:X} into V{:X}", y, x);
-                                internals.V[x] = internals.V[y];
-                            }
-                            1 => { // 0x8XY1 - stores the value of VX | VY into VX
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Adding V{:X}to V{:X} OR V{:X})",x,x,y);
-                                internals.V[x] |= internals.V[y];
-                                internals.V[0xF] = 0;
-                            },
-                            2 => { // 0x8XY2 - add value of VY to VX
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Set V{:X} to V{:X} AND V{:X}", x, x, y);
-                                internals.V[x] &= internals.V[y];
-                                internals.V[0xF] = 0;
-                            },
-                            3 => { // 0x8XY3 - XOR VY and X store in VX
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Set V{:X} to V{:X} XOR V{:X}", x, x, y);
-                                internals.V[x] ^= internals.V[y];
-                                internals.V[0xF] = 0;
-                            },
-                            4 => { // 0x8XY4 - Add VY to VX store carry in V15
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Add V{:X} to V{:X} and store carry in VF", y, x);
-                                internals.V[0xF] = if internals.V[x] as i32 + internals.V[y] as i32 > 255 {1} else {0};
-                                internals.V[x] = internals.V[x].wrapping_add(internals.V[y]);
-                            },
-                            5 => { // 0x8XY5 - Subtract VY from VX and store the borrow in V15
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Subtract V{:X} from V{:X} and store the borrow in VF" ,y ,x);
-                                internals.V[0xF] = if internals.V[x] > internals.V[y] {1} else {0};
-                                internals.V[x] = internals.V[x].wrapping_sub(internals.V[y]);
-                            },
-                            6 => { // 0x8X06 - Shift VX to right, first bit goes to V[15]
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Shift V{:X} to the right least significant bit goes to VF",x);
-                                internals.V[0xF] = internals.V[x] & 1;
-                                internals.V[x] >>= 1;
-                            },
-                            7 => { // 0x8XY7 - Subtract VX from VY result stored in VX and store the borrow in V15
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                let y = ((opcode & 0xF0) >> 4) as usize;
-                                opcode_description = format!("Subtract V{:X} from V{:X} store the result to V{:X} and store the borrow in VF" ,x ,y, x);
-                                internals.V[0xF] = if internals.V[y] > internals.V[x] {1} else {0};
-                                internals.V[x] = internals.V[y].wrapping_sub(internals.V[x]);
-                            },
-                            0xE => { // 0x8X0E - Shift VX to left,most significant bit goes to V15
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Shift V{:X} to the left most significant bit goes to VF",x);
-                                internals.V[0xF] = internals.V[x] >> 7;
-                                internals.V[x] <<= 1;
-                            },
-                            _ => {}
+
+                        internals.quirks = locked.quirks;
+                        if internals.pc_out_of_memory() {
+                            locked.freeze = true;
+                            break;
                         }
-                    },
-                    0x9 => { // 0x9XYN - Skip next instruction if Vx != VY
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let y = ((opcode & 0xF0) >> 4) as usize;
-                        opcode_description = format!("Skipping next instruction if V{:X} != V{:X}", x, y);
-                        if internals.V[x] != internals.V[y] {
-                            internals.PC += 2;
+                        let opcode = internals.fetch();
+
+                        let old_pc = internals.PC;
+                        let operands = Operands::capture(opcode, &internals);
+                        if internals.advance_pc() {
+                            locked.freeze = true;
+                            break;
                         }
-                    },
-                    0xA => { // 0xANNN - Put NNN into I
-                        let nnn = opcode & 0xFFF;
-                        opcode_description = format!("Put 0x{:03X} into I", nnn);
-                        internals.I = nnn;
-                    },
-                    0xB => {  // 0xBNNN - Jump to address NNN plus register V0
-                        let nnn = opcode & 0xFFF;
-                        opcode_description = format!("Jump to I + 0x{:03X}", nnn);
-                        internals.PC = nnn + internals.V[0] as u16;
-                    },
-                    0xC => { // 0xCXKK - Set VX to (random number between 0 - 255) & KK
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let kk= (opcode & 0xFF) as u8;
-                        let rnd = rand::thread_rng().gen_range(0..=255) as u8;
-                        opcode_description = format!("Set V{:X} to random number in [0,255] & 0x{:02X}", x, kk);
-                        internals.V[x] = rnd & kk;
-                    },  
-                    /*
-                    *
-                    *	Dxyn - DRW Vx, Vy, nibble
-                    *	Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-                    *	The interpreter reads n bytes from memory, starting at the address stored in I. These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). Sprites are XORed onto the existing screen.
-                    *	If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside the coordinates of the display, 
-                    *	it wraps around to the opposite side of the screen.
-                    *	
-                    *	A sprite is 8 bits of length and n bits of height
-                    *
-                    */
-                    0xD => {
-                        let x = ((opcode & 0xF00) >> 8) as usize;
-                        let y = ((opcode & 0xF0) >> 4) as usize;
-                        let n = opcode & 0xF;
-                        let sx = internals.V[x] as usize;
-                        let sy = internals.V[y] as usize;
+                        locked.coverage.record(old_pc);
+                        locked.profiler.record(opcode);
 
-                        opcode_description = format!("Draw sprite at {}, {} with length {}", sx,sy,n);
+                        let opcode_description = internals.execute(opcode, &mut gbuf, &key_states);
 
-                        internals.V[0xF] = 0;
+                        Emulator::send_state(locked, format!("{:04X}: {:04X} - {}", old_pc, opcode, opcode_description), &internals);
+                        executed += 1;
 
-                        for i in 0..n as usize {
-                            let pixel = internals.memory[internals.I as usize + i as usize];
-                            for j in 0..8usize {
-                                if pixel & (0b10000000 >> j) > 0 {
-                                    internals.V[0xF] = internals.V[0xF].max(gbuf[(j+sx)%64 + ((i+sy)%32)*64]);
-                                    gbuf[(j+sx)%64 + ((i+sy)%32)*64] ^= 1;
-                                }
+                        if let Some(target) = machine_code::call_target(opcode) {
+                            if internals.machine_code.get(target).is_none() {
+                                locked.unhandled_machine_code.entry(target).or_insert(old_pc);
                             }
                         }
-                    },
-                    0xE => {
-                        match opcode & 0xFF {
-                            0x9E => { // 0xEx9E - skip next instruction if key in Vx is pressed
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Skipping next instruction if key in V{:X} ({:X}) is pressed", x, internals.V[x]);
-                                if key_states[internals.V[x] as usize] {
-                                    internals.PC += 2;
-                                }
-                            },
-                            0xA1 => { // 0xEx9E - skip next instruction if key in Vx is pressed
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Skipping next instruction if key in V{:X} ({:X}) is not pressed", x, internals.V[x]);
-                                if !key_states[internals.V[x] as usize] {
-                                    internals.PC += 2;
-                                }
-                            },
-                            _ => {}
+                        if internals.fault.is_some() {
+                            locked.freeze = true;
+                            break;
                         }
-                    },
-                    0xF => {
-                        match opcode & 0xFF { // 0xFx07 - put delay timer into Vx
-                            0x7 => {
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Putting value of delay timer into V{:X}",x);
-                                internals.V[x] = internals.delay_timer;
-                            },
-                            0xA => { // 0xFx0A - Wait for key press store the value of the key in Vx
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Waiting for keypress and storing result into V{:X}", x);
-                                wfi_register = x as i8;
-                            },
-                            0x15 => { // 0xFx15 - Set delay timer to value of Vx
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Setting delay timer to the value of V{:X}", x);
-                                internals.delay_timer = internals.V[x];
-                            },
-                            0x18 => { // 0xFx18 - set sound timer value to Vx
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Setting sound timer to the value of V{:X}", x);
-                                internals.sound_timer = internals.V[x];
-                            },
-                            0x1E => { // 0xFx1E - value of Vx is added to I
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Adding the value of V{:X} to I", x);
-                                internals.I += internals.V[x] as u16;
-                            },
-                            0x29 => { // 0xFx29 - the value of I is set to sprite location of digit Vx
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Setting I to location of the sprite of the digit {:X}", x);
-                                internals.I = internals.V[x] as u16 * 5;
-                            },
-                            0x33 => { // 0xFx33 - store BCD represebtation of Vx in I
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Storing BCD representation of V{:X} into location I", x);
-                                internals.memory[internals.I as usize] = internals.V[x] / 100;
-                                internals.memory[internals.I as usize + 1] = (internals.V[x] / 10) % 10;
-                                internals.memory[internals.I as usize + 2] = internals.V[x] % 10;
-                            },
-                            0x55 => { // 0xFx55 - store the value of registers 0 to X into memory at I
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Storing values of register [0, {:X}] into memory at I", x);
-                                let mem_slice = &mut internals.memory[internals.I as usize..=internals.I as usize + x];
-                                let v_slice = &internals.V[0..=x];
-                                mem_slice.clone_from_slice(v_slice);
-                            },
-                            0x65 => { // 0xFx65 load registers from V0 to VX from location I
-                                let x = ((opcode & 0xF00) >> 8) as usize;
-                                opcode_description = format!("Loading values of register [0, {:X}] from address I", x);
-                                let v_slice = &mut internals.V[0..=x];
-                                let mem_slice = &internals.memory[internals.I as usize..=internals.I as usize + x];
-                                v_slice.clone_from_slice(mem_slice);
-                            },
-                            _ => {}
-                        }
-                    },
-                    _ => {}
-                }
 
-                
-                    
-                Emulator::send_state(locked, format!("{:04X}: {:04X} - {}", old_pc, opcode, opcode_description), &internals);
-                
+                        if vip_timing {
+                            vip_clock.charge(opcode, operands, &internals);
+                        }else if internals.quirks.display_wait && opcode >> 12 == 0xD {
+                            break;
+                        }
+                    }
 
-            };
-            clocked!(execute_opcodes, last_opcode_tick, 500);
-            
-            let mut execute_render = || {
-                if !frozen{
                     internals.delay_timer -= if internals.delay_timer > 0 {1} else {0};
                     internals.sound_timer -= if internals.sound_timer > 0 {1} else {0};
+                    locked.recent_draws.push_front(std::mem::take(&mut internals.sprite_draws));
+                    locked.recent_draws.truncate(DRAW_HISTORY_FRAMES);
+                    // memory can be 64K, so the state is only handed over once per frame instead of after every instruction
+                    locked.internal_state.clone_from(&internals);
+                    Some(executed)
+                };
+                // frames run with frame advance get recorded too, only frames that didn't run are left out
+                if let Some(executed) = execute_frame() {
+                    scheduler.record_frame(executed);
+                    if let Some((recording, post_processor)) = &mut recorder {
+                        let locked = &mut self.ui_interface.inter_thread.lock();
+                        let post_processing = locked.display_settings.post_processing;
+                        let frame = post_processor.process(&gbuf, &locked.palette, &post_processing);
+                        if let Err(e) = recording.record(&frame, &post_processing, internals.sound_timer > 0) {
+                            locked.capture_status = format!("Recording failed: {}", e);
+                            locked.recording = false;
+                            recorder = None;
+                        }
+                    }
+                }
+            }
+
+            // while running unthrottled the screen still only gets drawn at the normal frame rate
+            if frames > 0 && (!unthrottled || now.duration_since(last_render).as_secs_f32() >= 1f32 / TIMER_FREQUENCY as f32) {
+                last_render = now;
+                if let Some(context) = &mut self.context {
+                    if speed_title != self.window_title {
+                        self.window_title.clone_from(&speed_title);
+                        context.canvas.window_mut().set_title(&speed_title).unwrap();
+                    }
                 }
-                self.render_graphics(&gbuf);
+                let (frame, settings) = {
+                    let locked = &mut self.ui_interface.inter_thread.lock();
+                    let frame = post_processor.process(&gbuf, &locked.palette, &locked.display_settings.post_processing);
+                    locked.display.clone_from(&gbuf);
+                    locked.display_frame = frame;
+                    if locked.screenshot_requested {
+                        locked.screenshot_requested = false;
+                        let path = capture::capture_path(&self.ui_interface.target_file, locked.screenshot_format.extension());
+                        locked.capture_status = match capture::save_screenshot(&path, &frame, locked.capture_scale, &locked.display_settings.post_processing) {
+                            Ok(()) => format!("Saved {}", path),
+                            Err(e) => format!("Screenshot failed: {}", e),
+                        };
+                    }
+                    (frame, locked.display_settings)
+                };
+                self.render_graphics(&frame, &settings);
                 self.ui_interface.egui_ctx.request_repaint();
+            }
+
+            if scheduler.measure(now) {
+                let locked = &mut self.ui_interface.inter_thread.lock();
+                locked.measured_fps = scheduler.fps;
+                locked.measured_ips = scheduler.ips;
+            }
+
+            if !unthrottled {
+                thread::sleep(scheduler.until_next_frame(Instant::now()));
+            }
+        }
+
+        if let Some((recorder, _)) = recorder {
+            let locked = &mut self.ui_interface.inter_thread.lock();
+            let path = recorder.path().to_owned();
+            locked.capture_status = match recorder.finish() {
+                Ok(frames) => format!("Saved {} frames to {}", frames, path),
+                Err(e) => format!("Recording failed: {}", e),
             };
-            clocked!(execute_render, last_render_tick, 60);
+            locked.recording = false;
+        }
+
+        if let Err(e) = self.ui_interface.inter_thread.lock().display_settings.save() {
+            eprintln!("{}", e);
         }
     }
 
-    fn render_graphics(&mut self, gbuf: &[u8; 64*32]){
-        let canvas = &mut self.context.canvas;
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        for i in 0..64usize{
-            for j in 0..32usize{
-                let pixel: u8 = gbuf[i+j*64] * 255;
-                canvas.set_draw_color(Color::RGB(pixel, pixel, pixel));
-                canvas.draw_point(Point::new(i as i32, j as i32)).unwrap();
+    /// Window title that shows the speed the emulator is running at
+    fn speed_title(instructions_per_frame: u32, cosmac_vip: bool, vip_timing: bool, fast_forward: bool, slow_motion: bool, slow_motion_factor: f32) -> String {
+        let mut title = if cosmac_vip {
+            format!("{} - COSMAC VIP", WINDOW_TITLE)
+        }else if vip_timing {
+            format!("{} - COSMAC VIP timing", WINDOW_TITLE)
+        }else{
+            format!("{} - {} instructions/frame ({} Hz)", WINDOW_TITLE, instructions_per_frame, instructions_per_frame * TIMER_FREQUENCY)
+        };
+        if fast_forward {
+            title.push_str(" - fast forward");
+        }else if slow_motion {
+            title.push_str(&format!(" - slow motion {:.0}%", slow_motion_factor * 100f32));
+        }
+        title
+    }
+
+    fn render_graphics(&mut self, frame: &Frame, settings: &DisplaySettings){
+        let canvas = match &mut self.context {
+            Some(context) => &mut context.canvas,
+            None => return,
+        };
+        let fullscreen = if settings.fullscreen {FullscreenType::Desktop} else {FullscreenType::Off};
+        if canvas.window().fullscreen_state() != fullscreen {
+            if let Err(e) = canvas.window_mut().set_fullscreen(fullscreen) {
+                // turning the setting off keeps a failed switch to fullscreen from being retried every frame
+                eprintln!("Couldn't switch fullscreen: {}", e);
+                self.ui_interface.inter_thread.lock().display_settings.fullscreen = false;
             }
         }
+
+        // everything around the screen is letterboxed in black
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        let (width, height) = canvas.output_size().unwrap();
+        let [x, y, width, height] = settings.scale_mode.fit(width as f32, height as f32);
+
+        // the frame is blown up to about the size it's shown at so scanlines come out a single pixel high
+        let scale = ((width / 64f32).round() as usize).clamp(1, postprocess::MAX_SCALE);
+        let pixels = postprocess::scale(frame, scale, &settings.post_processing);
+        let texture_creator = canvas.texture_creator();
+        let mut texture = texture_creator.create_texture_static(PixelFormatEnum::RGB24, 64 * scale as u32, 32 * scale as u32).unwrap();
+        texture.update(None, &pixels, 64 * scale * 3).unwrap();
+        canvas.copy(&texture, None, Rect::new(x as i32, y as i32, width as u32, height as u32)).unwrap();
         canvas.present();
     }
 
 }
 
 
-pub fn start_thread(kill_receiver: Receiver<bool>, egui_ctx: egui::Context, inter_thread: Arc<Mutex<InterThreadData>>) -> thread::JoinHandle<()>{
+pub fn start_thread(kill_receiver: Receiver<bool>, target_file: String, egui_ctx: egui::Context, inter_thread: Arc<Mutex<InterThreadData>>) -> thread::JoinHandle<()>{
     thread::spawn(move || {
-        let mut emulator = Emulator::new(kill_receiver, (r"C:\C8Games\Tank.ch8").to_owned(), egui_ctx, inter_thread);
+        let mut emulator = Emulator::new(kill_receiver, target_file, egui_ctx, inter_thread);
         emulator.start();
     })
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::MemorySize;
+
+    /// Interpreter with the program loaded at the program start
+    fn setup(config: MachineConfig, program: &[u8]) -> C8 {
+        let mut internals = C8::new(config);
+        let start = config.program_start as usize;
+        internals.memory[start..start + program.len()].clone_from_slice(program);
+        internals
+    }
+
+    /// Runs instructions like the frame loop does, stops early at a fault
+    fn run(internals: &mut C8, instructions: usize, key_states: &[bool; 16]) {
+        let mut gbuf = [0u8; 64*32];
+        for _ in 0..instructions {
+            if internals.pc_out_of_memory() {
+                return;
+            }
+            let opcode = internals.fetch();
+            if internals.advance_pc() {
+                return;
+            }
+            internals.execute(opcode, &mut gbuf, key_states);
+            if internals.fault.is_some() {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn key_skips_only_look_at_the_low_nibble() {
+        // V0 = 0x20, SKP V0
+        let mut internals = setup(MachineConfig::default(), &[0x60, 0x20, 0xE0, 0x9E]);
+        let mut key_states = [false; 16];
+        key_states[0] = true;
+        run(&mut internals, 2, &key_states);
+        assert_eq!(internals.PC, 0x206);
+    }
+
+    #[test]
+    fn programs_are_cut_off_at_the_end_of_memory() {
+        let path = std::env::temp_dir().join(format!("chip8-emulator-{}-load.ch8", std::process::id()));
+        fs::write(&path, [0x12; 8]).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut internals = C8::new(MachineConfig { program_start: 0x7FC, memory_size: MemorySize::TwoK, ..MachineConfig::default() });
+        assert_eq!(internals.load_program(path).unwrap(), 0x7FC..0x800);
+        let mut internals = C8::new(MachineConfig { program_start: 0x900, memory_size: MemorySize::TwoK, ..MachineConfig::default() });
+        assert_eq!(internals.load_program(path).unwrap(), 0x800..0x800);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn adding_to_i_past_0xffff_faults() {
+        let config = MachineConfig { memory_size: MemorySize::SixtyFourK, ..MachineConfig::default() };
+        // LD I, 0x000; VF = 0xFF; ADD I, VF twice; JP 0x204
+        let mut internals = setup(config, &[0xA0, 0x00, 0x6F, 0xFF, 0xFF, 0x1E, 0xFF, 0x1E, 0x12, 0x04]);
+        run(&mut internals, 1000, &[false; 16]);
+        assert_eq!(internals.I, 0xFFFF, "257 times 0xFF, the next add faults");
+        assert!(internals.fault.as_ref().is_some_and(|fault| fault.starts_with("I ran past the end of 64K memory")));
+
+        // the store itself fits right up to the end, only the increment doesn't
+        let mut internals = setup(config, &[0xFF, 0x55]);
+        internals.quirks.memory_increment = true;
+        internals.I = 0xFFF0;
+        internals.V[0xF] = 0xAB;
+        run(&mut internals, 1, &[false; 16]);
+        assert_eq!(internals.memory[0xFFFF], 0xAB);
+        assert!(internals.fault.is_some());
+
+        let mut internals = setup(config, &[0xFE, 0x65]);
+        internals.quirks.memory_increment = true;
+        internals.I = 0xFFF0;
+        run(&mut internals, 1, &[false; 16]);
+        assert_eq!((internals.fault, internals.I), (None, 0xFFFF));
+    }
+
+    #[test]
+    fn glyphs_past_the_end_of_memory_fault() {
+        let config = MachineConfig { memory_size: MemorySize::SixtyFourK, ..MachineConfig::default() };
+        // V0 = 0xFF, LD HF, V0
+        let mut internals = setup(config, &[0x60, 0xFF, 0xF0, 0x30]);
+        internals.font_address = 0xFF00;
+        run(&mut internals, 2, &[false; 16]);
+        assert!(internals.fault.as_ref().is_some_and(|fault| fault.starts_with("I ran past the end of 64K memory")));
+
+        // LD F, V0 still fits
+        let mut internals = setup(config, &[0x60, 0x0F, 0xF0, 0x29]);
+        internals.font_address = 0xFF00;
+        run(&mut internals, 2, &[false; 16]);
+        assert_eq!(internals.fault, None);
+        assert_eq!(internals.I, 0xFF00 + 15 * 5);
+    }
+}
@@ -1,6 +1,7 @@
 use egui::{Ui};
 use egui::mutex::Mutex;
 use core::panic;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::sync::Arc;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Sender};
@@ -8,6 +9,22 @@ use std::thread::JoinHandle;
 use sdl2::keyboard::Keycode;
 
 use crate::emulator;
+use crate::vip_timing;
+use crate::analyzer::{Analysis, DisassemblyLine};
+use crate::coverage::Coverage;
+use crate::database::{Database, RomEntry};
+use crate::fonts::{self, Font, FontSet};
+use crate::machine::{MachineConfig, MemorySize};
+use crate::machine_code::{self, MachineCode};
+use crate::detector::Detection;
+use crate::display::{DisplaySettings, ScaleMode};
+use crate::capture::ImageFormat;
+use crate::recording::VideoFormat;
+use crate::palette::{self, Palette, PaletteOverrides, PaletteSet};
+use crate::postprocess::{self, Frame, PostProcessing};
+use crate::quirks::{Platform, Quirks};
+use crate::profiler::{Profiler, ProfilerSort};
+use crate::sprites::{self, SpriteSheet, SpriteSize};
 
 /// Holds open/closed states of all ui windows
 struct WindowStates {
@@ -16,26 +33,116 @@ struct WindowStates {
     internals: bool,
     memory: bool,
     keybinds: bool,
+    coverage: bool,
+    profiler: bool,
+    disassembly: bool,
+    platform: bool,
+    database: bool,
+    machine_code: bool,
+    font_editor: bool,
+    display: bool,
+    palette: bool,
+    sprite_viewer: bool,
+    sprite_editor: bool,
 }
 
 impl Default for WindowStates {
     fn default() -> Self {
-        Self { control_panel: true, opcodes_view: false, internals: false, memory: false, keybinds: false }
+        Self { control_panel: true, opcodes_view: false, internals: false, memory: false, keybinds: false, coverage: false, profiler: false, disassembly: false, platform: false, database: false, machine_code: false, font_editor: false, display: true, palette: false, sprite_viewer: false, sprite_editor: false }
     }
 }
 
 struct UIStates {
-    memory_slider: i32,
+    /// First address shown by the memory window
+    memory_start: i32,
     keymap: [i32; 16],
     listen_for_key: i32,
+    rom_path: String,
+    listing_path: String,
+    listing_status: String,
+    profile_path: String,
+    profile_status: String,
+    profiler_sort: ProfilerSort,
+    profiler_descending: bool,
+    dot_path: String,
+    dot_status: String,
+    follow_pc: bool,
+    database_path: String,
+    database_status: String,
+    /// Glyph the font editor shows
+    font_glyph: usize,
+    font_large: bool,
+    font_path: String,
+    font_status: String,
+    /// Framebuffer uploaded for the display window
+    display_texture: Option<egui::TextureHandle>,
+    /// Keys held down while the display had the keyboard, used to drop key repeats
+    display_keys: HashSet<egui::Key>,
+    palette_status: String,
+    /// Pixel clicked in the display, as an index into the screen buffer
+    inspected_pixel: Option<usize>,
+    /// Address the disassembly scrolls to once
+    disassembly_target: Option<u16>,
+    sprite_sheet: SpriteSheet,
+    /// The sprite viewer starts at I
+    sprite_follow_i: bool,
+    /// Positions of the sprites picked in the sprite viewer
+    selected_sprites: BTreeSet<usize>,
+    sprite_path: String,
+    sprite_status: String,
+    /// Memory the sprite editor shows, always a single sprite
+    editor_sheet: SpriteSheet,
+    /// Bytes changed by the sprite editor since the rom was started, by address
+    sprite_edits: BTreeMap<u16, u8>,
+    /// Rom the edits were made to, the rom path can be changed while it's running
+    edited_rom_path: String,
+    patch_path: String,
+    patch_status: String,
+    /// Outline the sprites drawn in the display
+    sprite_overlay: bool,
+    /// Frames the outlines of older sprites take to fade out
+    overlay_fade_frames: usize,
 }
 
 impl Default for UIStates{
     fn default() -> Self {
         Self { 
-            memory_slider: 3840, 
+            memory_start: 0,
             keymap: UIStates::keymap_default(),
             listen_for_key: -1,
+            rom_path: r"C:\C8Games\Tank.ch8".to_owned(),
+            listing_path: "coverage.lst".to_owned(),
+            listing_status: String::new(),
+            profile_path: "profile.folded".to_owned(),
+            profile_status: String::new(),
+            profiler_sort: ProfilerSort::InclusiveCycles,
+            profiler_descending: true,
+            dot_path: "cfg.dot".to_owned(),
+            dot_status: String::new(),
+            follow_pc: true,
+            database_path: "programs.json".to_owned(),
+            database_status: String::new(),
+            font_glyph: 0,
+            font_large: false,
+            font_path: "font.bin".to_owned(),
+            font_status: String::new(),
+            display_texture: None,
+            display_keys: HashSet::new(),
+            palette_status: String::new(),
+            inspected_pixel: None,
+            disassembly_target: None,
+            sprite_sheet: SpriteSheet::default(),
+            sprite_follow_i: true,
+            selected_sprites: BTreeSet::new(),
+            sprite_path: "sprites.png".to_owned(),
+            sprite_status: String::new(),
+            editor_sheet: SpriteSheet { count: 1, ..SpriteSheet::default() },
+            sprite_edits: BTreeMap::new(),
+            edited_rom_path: String::new(),
+            patch_path: "patched.ch8".to_owned(),
+            patch_status: String::new(),
+            sprite_overlay: false,
+            overlay_fade_frames: 0,
         }
     }
 }
@@ -46,6 +153,63 @@ impl UIStates {
         Some(keycode.name())
     }
 
+    /// SDL keycode of a key pressed while the display has the keyboard, so the emulator handles both windows' keys the same way
+    fn keycode_from_egui(key: egui::Key) -> Keycode {
+        match key {
+            egui::Key::ArrowDown => Keycode::Down,
+            egui::Key::ArrowLeft => Keycode::Left,
+            egui::Key::ArrowRight => Keycode::Right,
+            egui::Key::ArrowUp => Keycode::Up,
+            egui::Key::Escape => Keycode::Escape,
+            egui::Key::Tab => Keycode::Tab,
+            egui::Key::Backspace => Keycode::Backspace,
+            egui::Key::Enter => Keycode::Return,
+            egui::Key::Space => Keycode::Space,
+            egui::Key::Insert => Keycode::Insert,
+            egui::Key::Delete => Keycode::Delete,
+            egui::Key::Home => Keycode::Home,
+            egui::Key::End => Keycode::End,
+            egui::Key::PageUp => Keycode::PageUp,
+            egui::Key::PageDown => Keycode::PageDown,
+            egui::Key::Num0 => Keycode::Num0,
+            egui::Key::Num1 => Keycode::Num1,
+            egui::Key::Num2 => Keycode::Num2,
+            egui::Key::Num3 => Keycode::Num3,
+            egui::Key::Num4 => Keycode::Num4,
+            egui::Key::Num5 => Keycode::Num5,
+            egui::Key::Num6 => Keycode::Num6,
+            egui::Key::Num7 => Keycode::Num7,
+            egui::Key::Num8 => Keycode::Num8,
+            egui::Key::Num9 => Keycode::Num9,
+            egui::Key::A => Keycode::A,
+            egui::Key::B => Keycode::B,
+            egui::Key::C => Keycode::C,
+            egui::Key::D => Keycode::D,
+            egui::Key::E => Keycode::E,
+            egui::Key::F => Keycode::F,
+            egui::Key::G => Keycode::G,
+            egui::Key::H => Keycode::H,
+            egui::Key::I => Keycode::I,
+            egui::Key::J => Keycode::J,
+            egui::Key::K => Keycode::K,
+            egui::Key::L => Keycode::L,
+            egui::Key::M => Keycode::M,
+            egui::Key::N => Keycode::N,
+            egui::Key::O => Keycode::O,
+            egui::Key::P => Keycode::P,
+            egui::Key::Q => Keycode::Q,
+            egui::Key::R => Keycode::R,
+            egui::Key::S => Keycode::S,
+            egui::Key::T => Keycode::T,
+            egui::Key::U => Keycode::U,
+            egui::Key::V => Keycode::V,
+            egui::Key::W => Keycode::W,
+            egui::Key::X => Keycode::X,
+            egui::Key::Y => Keycode::Y,
+            egui::Key::Z => Keycode::Z,
+        }
+    }
+
     fn key_from_name(name: String) -> Option<Keycode> {
         if name.len() == 4 && name[0..=2].eq("Num") {
             let name = &name.chars().nth(3).unwrap().to_string();
@@ -82,7 +246,89 @@ pub struct InterThreadData{
     pub executed_instructions: Vec<String>,
     pub internal_state: emulator::C8,
     pub freeze: bool,
-    pub keymap: [i32; 16]
+    pub keymap: [i32; 16],
+    pub coverage: Coverage,
+    pub profiler: Profiler,
+    pub analysis: Analysis,
+    /// Platform the quirks were picked for
+    pub platform: Platform,
+    /// Quirks the emulator follows, can be changed while it's running
+    pub quirks: Quirks,
+    /// Platform suggested for the loaded program
+    pub detection: Option<Detection>,
+    /// Apply the suggested platform as soon as a program is loaded
+    pub apply_detection: bool,
+    pub database: Database,
+    /// Database entry of the loaded program
+    pub rom_entry: Option<RomEntry>,
+    /// Apply the settings the database recommends as soon as a program is loaded
+    pub apply_database: bool,
+    pub instructions_per_frame: u32,
+    /// Charge every instruction the machine cycles it takes on the COSMAC VIP instead of running a fixed amount per frame
+    pub vip_timing: bool,
+    /// Run the program on the original interpreter on an emulated COSMAC VIP
+    pub cosmac_vip: bool,
+    /// CHIP-8 interpreter image loaded at 0x0000 of the VIP
+    pub vip_interpreter_path: String,
+    /// Monitor ROM mapped at 0x8000 of the VIP, left out if empty
+    pub vip_monitor_path: String,
+    /// Why the COSMAC VIP couldn't be started
+    pub vip_status: String,
+    pub font_set: FontSet,
+    pub font: Font,
+    pub font_address: u16,
+    /// Set when the font changed, cleared by the emulator once it loaded the font into memory
+    pub font_changed: bool,
+    /// Bytes the sprite editor changed as address and value, the emulator writes them into memory and empties the list
+    pub memory_writes: Vec<(u16, u8)>,
+    /// Memory layout the next program gets loaded with
+    pub machine_config: MachineConfig,
+    /// Routines 0NNN calls are handled by
+    pub machine_code: MachineCode,
+    /// Addresses the program called with 0NNN that have no routine, along with the first call's address
+    pub unhandled_machine_code: BTreeMap<u16, u16>,
+    /// Run as fast as possible
+    pub fast_forward: bool,
+    pub slow_motion: bool,
+    /// Fraction of the normal speed slow motion runs at
+    pub slow_motion_factor: f32,
+    /// Frames per second the emulator actually ran at
+    pub measured_fps: f32,
+    /// Instructions per second the emulator actually ran at
+    pub measured_ips: f32,
+    /// Set to run a single frame while frozen, cleared by the emulator once the frame ran
+    pub frame_advance: bool,
+    /// Open the standalone SDL window next to the ui
+    pub sdl_window: bool,
+    /// Screen as last drawn by the emulator
+    pub display: [u8; 64*32],
+    /// The screen after post-processing, as it is shown
+    pub display_frame: Frame,
+    /// Colours the emulator draws with, replaced by the rom's own palette when a rom is loaded
+    pub palette: Palette,
+    pub palette_set: PaletteSet,
+    pub palette_overrides: PaletteOverrides,
+    /// SHA-1 of the loaded rom, palettes are picked for roms by it
+    pub rom_hash: String,
+    pub display_settings: DisplaySettings,
+    /// Keys pressed or released in the ui, handed to the emulator the same way SDL key events are
+    pub key_events: Vec<emulator::KeyEvent>,
+    /// Set to save a screenshot of the next frame, cleared by the emulator once it's saved
+    pub screenshot_requested: bool,
+    pub screenshot_format: ImageFormat,
+    /// Set to start or stop recording, cleared by the emulator once it did
+    pub recording_requested: bool,
+    /// Set by the emulator while it records
+    pub recording: bool,
+    pub recording_format: VideoFormat,
+    /// Write the beeper to a WAV file next to the recording
+    pub record_audio: bool,
+    /// Every pixel of screenshots and recordings becomes a square this large
+    pub capture_scale: usize,
+    /// Outcome of the last screenshot or recording
+    pub capture_status: String,
+    /// Sprites drawn during the last frames, the newest frame comes first
+    pub recent_draws: VecDeque<Vec<emulator::SpriteDraw>>,
 }
 
 impl InterThreadData{
@@ -92,6 +338,54 @@ impl InterThreadData{
             internal_state: emulator::C8::default(),
             freeze: false,
             keymap: UIStates::keymap_default(),
+            coverage: Coverage::new(emulator::PROGRAM_START, 0, 0),
+            profiler: Profiler::default(),
+            analysis: Analysis::analyze(&[], emulator::PROGRAM_START, 0),
+            platform: Platform::Chip8,
+            quirks: Quirks::default(),
+            detection: None,
+            apply_detection: false,
+            database: Database::load(),
+            rom_entry: None,
+            apply_database: true,
+            instructions_per_frame: emulator::DEFAULT_INSTRUCTIONS_PER_FRAME,
+            vip_timing: false,
+            cosmac_vip: false,
+            font_set: FontSet::SuperChip,
+            font: Font::default(),
+            font_address: fonts::DEFAULT_FONT_ADDRESS,
+            font_changed: false,
+            memory_writes: vec![],
+            machine_config: MachineConfig::default(),
+            machine_code: MachineCode::default(),
+            unhandled_machine_code: BTreeMap::new(),
+            vip_interpreter_path: "chip8.bin".to_owned(),
+            vip_monitor_path: String::new(),
+            vip_status: String::new(),
+            fast_forward: false,
+            slow_motion: false,
+            slow_motion_factor: 0.25,
+            measured_fps: 0f32,
+            measured_ips: 0f32,
+            frame_advance: false,
+            sdl_window: false,
+            display: [0; 64*32],
+            display_frame: [[0; 3]; 64*32],
+            palette: Palette::default(),
+            palette_set: PaletteSet::BlackAndWhite,
+            palette_overrides: PaletteOverrides::load(),
+            rom_hash: String::new(),
+            display_settings: DisplaySettings::load(),
+            key_events: vec![],
+            screenshot_requested: false,
+            screenshot_format: ImageFormat::Png,
+            recording_requested: false,
+            recording: false,
+            recording_format: VideoFormat::Gif,
+            record_audio: false,
+            capture_scale: 4,
+            capture_status: String::new(),
+            recent_draws: VecDeque::new(),
         }
     }
 }
@@ -120,7 +414,7 @@ impl EmulatorInterface{
         handle.join().unwrap();
     }
 
-    fn start(&mut self, egui_ctx: &egui::Context, keymap: &[i32; 16]) {
+    fn start(&mut self, egui_ctx: &egui::Context, target_file: &str, keymap: &[i32; 16]) {
         if let Some(_) = self.emulator_handle{
             if self.status() {
                 panic!("Attempted to start emulator while already running");
@@ -133,7 +427,7 @@ impl EmulatorInterface{
         }
         let kill_channel = channel();
         self.kill_sender = Some(kill_channel.0);
-        self.emulator_handle = Some(emulator::start_thread(kill_channel.1, egui_ctx.clone(), self.inter_thread.clone()));
+        self.emulator_handle = Some(emulator::start_thread(kill_channel.1, target_file.to_owned(), egui_ctx.clone(), self.inter_thread.clone()));
     }
     
     fn kill(&mut self){
@@ -165,6 +459,112 @@ pub struct EmulatorUI {
 }
 
 impl EmulatorUI {
+    /// Scale the framebuffer is uploaded at, the texture gets filtered linearly so the pixels are blown up beforehand
+    const DISPLAY_TEXTURE_SCALE: usize = 8;
+    /// Leaves fullscreen, egui doesn't know about F11 which toggles it in the SDL window
+    const LEAVE_FULLSCREEN_KEY: egui::Key = egui::Key::Escape;
+    /// Saves a screenshot along with Ctrl, egui doesn't know about F12 which does the same in the SDL window
+    const SCREENSHOT_KEY: egui::Key = egui::Key::S;
+    /// Starts and stops recording along with Ctrl, like F9 in the SDL window
+    const RECORD_KEY: egui::Key = egui::Key::R;
+    /// Size of a sprite pixel in the sprite viewer
+    const SPRITE_PIXEL_SIZE: f32 = 4f32;
+
+    /// Uploads the screen the emulator drew last
+    fn update_display_texture(&mut self, ctx: &egui::Context) -> egui::TextureId {
+        let image = {
+            let locked = self.emulator_interface.inter_thread.lock();
+            EmulatorUI::display_image(&locked.display_frame, &locked.display_settings.post_processing)
+        };
+        let texture = self.ui_states.display_texture.get_or_insert_with(|| ctx.load_texture("display", image.clone()));
+        texture.set(image);
+        texture.id()
+    }
+
+    /// Fills the rect with the screen scaled as the mode says and letterboxes the rest, returns where the screen ended up
+    fn paint_display(ui: &Ui, texture: egui::TextureId, rect: egui::Rect, scale_mode: ScaleMode) -> egui::Rect {
+        let [x, y, width, height] = scale_mode.fit(rect.width(), rect.height());
+        let screen = egui::Rect::from_min_size(rect.min + egui::vec2(x, y), egui::vec2(width, height));
+        let uv = egui::Rect::from_min_max(egui::pos2(0f32, 0f32), egui::pos2(1f32, 1f32));
+        ui.painter().rect_filled(rect, 0f32, egui::Color32::BLACK);
+        ui.painter().add(egui::Shape::image(texture, screen, uv, egui::Color32::WHITE));
+        screen
+    }
+
+    /// Outlines the sprites drawn during the last frames on the screen, older frames fade out and draws that set VF are red
+    fn paint_sprite_boxes(ui: &Ui, screen: egui::Rect, recent_draws: &VecDeque<Vec<emulator::SpriteDraw>>, fade_frames: usize, clipping: bool) {
+        let painter = ui.painter_at(screen);
+        let pixel = egui::vec2(screen.width() / 64f32, screen.height() / 32f32);
+        // oldest first so the newest boxes end up on top
+        for (age, draws) in recent_draws.iter().enumerate().take(fade_frames + 1).rev() {
+            let alpha = 1f32 - age as f32 / (fade_frames + 1) as f32;
+            for draw in draws.iter().filter(|draw| draw.height > 0) {
+                let color = if draw.collision {egui::Color32::RED} else {egui::Color32::LIGHT_GREEN};
+                let stroke = egui::Stroke::new(1f32, color.linear_multiply(alpha));
+                let size = egui::vec2(8f32 * pixel.x, draw.height as f32 * pixel.y);
+                // without clipping the parts past the edges wrap around to the other side
+                let wraps: &[(f32, f32)] = if clipping {&[(0f32, 0f32)]} else {&[(0f32, 0f32), (-64f32, 0f32), (0f32, -32f32), (-64f32, -32f32)]};
+                for (x_offset, y_offset) in wraps {
+                    let min = screen.min + egui::vec2((draw.x as f32 + x_offset) * pixel.x, (draw.y as f32 + y_offset) * pixel.y);
+                    painter.rect_stroke(egui::Rect::from_min_size(min, size), 0f32, stroke);
+                }
+            }
+        }
+    }
+
+    /// Index of the screen buffer pixel under the position, if it's on the screen
+    fn pixel_at(screen: egui::Rect, pos: egui::Pos2) -> Option<usize> {
+        if !screen.contains(pos) {
+            return None;
+        }
+        let x = ((pos.x - screen.min.x) / screen.width() * 64f32) as usize;
+        let y = ((pos.y - screen.min.y) / screen.height() * 32f32) as usize;
+        Some(x.min(63) + y.min(31) * 64)
+    }
+
+    fn display_image(frame: &Frame, post_processing: &PostProcessing) -> egui::ColorImage {
+        let scale = EmulatorUI::DISPLAY_TEXTURE_SCALE;
+        let pixels = postprocess::scale(frame, scale, post_processing);
+        egui::ColorImage {
+            size: [64 * scale, 32 * scale],
+            pixels: pixels.chunks(3).map(|rgb| egui::Color32::from_rgb(rgb[0], rgb[1], rgb[2])).collect(),
+        }
+    }
+
+    /// Hands the keys pressed while no widget wants the keyboard to the emulator
+    fn route_keys(&mut self, ctx: &egui::Context) {
+        let mut locked = self.emulator_interface.inter_thread.lock();
+        for event in ctx.input().events.iter() {
+            if let egui::Event::Key { key, pressed, modifiers } = event {
+                // egui repeats held keys as presses
+                if *pressed && !self.ui_states.display_keys.insert(*key) || !*pressed && !self.ui_states.display_keys.remove(key) {
+                    continue;
+                }
+                if modifiers.command && (*key == EmulatorUI::SCREENSHOT_KEY || *key == EmulatorUI::RECORD_KEY) {
+                    if *pressed && *key == EmulatorUI::SCREENSHOT_KEY {
+                        locked.screenshot_requested = true;
+                    }else if *pressed {
+                        locked.recording_requested = true;
+                    }
+                    continue;
+                }
+                let keycode = UIStates::keycode_from_egui(*key);
+                locked.key_events.push(emulator::KeyEvent { keycode, pressed: *pressed, repeat: false });
+            }
+        }
+    }
+
+    /// Releases the keys still held down once the display loses the keyboard
+    fn release_keys(&mut self) {
+        if self.ui_states.display_keys.is_empty() {
+            return;
+        }
+        let mut locked = self.emulator_interface.inter_thread.lock();
+        for key in self.ui_states.display_keys.drain() {
+            locked.key_events.push(emulator::KeyEvent { keycode: UIStates::keycode_from_egui(key), pressed: false, repeat: false });
+        }
+    }
+
     /// Draws a button that controls open/closed state of a window that the window_state belongs to
     #[inline]
     fn create_window_toggle(ui: &mut Ui, window_state: &mut bool, name: &str) {
@@ -185,9 +585,18 @@ impl Default for EmulatorUI {
 }
 
 impl eframe::App for EmulatorUI {
+    fn on_exit(&mut self, _gl: &eframe::glow::Context) {
+        if let Err(e) = self.emulator_interface.inter_thread.lock().display_settings.save() {
+            eprintln!("{}", e);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
      
         ctx.set_visuals(egui::Visuals::dark());   // dark theme
+
+        let window_size = ctx.input().screen_rect().size();
+        self.emulator_interface.inter_thread.lock().display_settings.ui_window_size = [window_size.x, window_size.y];
         
         // <background and menu bar>
         egui::CentralPanel::default()
@@ -198,6 +607,17 @@ impl eframe::App for EmulatorUI {
                     EmulatorUI::create_window_toggle(ui, &mut self.window_states.internals, "Internals");
                     EmulatorUI::create_window_toggle(ui, &mut self.window_states.memory, "Memory");
                     EmulatorUI::create_window_toggle(ui, &mut self.window_states.keybinds, "Keybinds");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.coverage, "Coverage");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.profiler, "Profiler");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.disassembly, "Disassembly");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.platform, "Platform");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.database, "Database");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.machine_code, "Machine Code");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.font_editor, "Font Editor");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.display, "Display");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.palette, "Palette");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.sprite_viewer, "Sprite Viewer");
+                    EmulatorUI::create_window_toggle(ui, &mut self.window_states.sprite_editor, "Sprite Editor");
                 });
             });
         // </background and menu bar>
@@ -220,28 +640,146 @@ impl eframe::App for EmulatorUI {
                     if should_start {
                         ui.colored_label(egui::Color32::LIGHT_RED, "Inactive");
                     }else{
-                        if self.emulator_interface.inter_thread.lock().freeze {
+                        let locked = self.emulator_interface.inter_thread.lock();
+                        if locked.freeze {
                             ui.colored_label(egui::Color32::LIGHT_BLUE, "Frozen");
+                        }else if locked.internal_state.key_wait.is_some() {
+                            ui.colored_label(egui::Color32::YELLOW, "Waiting for key");
                         }else{
                             ui.colored_label(egui::Color32::LIGHT_GREEN, "Running");
                         }
                     }
                 });
+                if !should_start {
+                    let locked = self.emulator_interface.inter_thread.lock();
+                    if let Some(fault) = &locked.internal_state.fault {
+                        ui.colored_label(egui::Color32::LIGHT_RED, format!("Fault: {}", fault));
+                    }
+                    if let Some(key_wait) = &locked.internal_state.key_wait {
+                        ui.label(key_wait.description());
+                    }
+                    if !locked.unhandled_machine_code.is_empty() {
+                        ui.colored_label(egui::Color32::YELLOW, format!("Warning: {} unhandled 0NNN machine code calls, see Machine Code", locked.unhandled_machine_code.len()));
+                    }
+                }
                 // </emulator status>
 
                 ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
 
+                ui.horizontal(|ui| {
+                    ui.label("ROM: ");
+                    ui.add_enabled(should_start, egui::TextEdit::singleline(&mut self.ui_states.rom_path));
+                });
+
+                // <cosmac vip>
+                {
+                    let locked = &mut *self.emulator_interface.inter_thread.lock();
+                    ui.add_enabled(should_start, egui::Checkbox::new(&mut locked.cosmac_vip, "Run on an emulated COSMAC VIP"));
+                    if locked.cosmac_vip {
+                        ui.horizontal(|ui| {
+                            ui.label("Interpreter: ");
+                            ui.add_enabled(should_start, egui::TextEdit::singleline(&mut locked.vip_interpreter_path));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Monitor ROM: ");
+                            ui.add_enabled(should_start, egui::TextEdit::singleline(&mut locked.vip_monitor_path).hint_text("optional"));
+                        });
+                    }
+                    if !locked.vip_status.is_empty() {
+                        ui.label(&locked.vip_status);
+                    }
+                }
+                // </cosmac vip>
+
+                {
+                    let locked = &mut *self.emulator_interface.inter_thread.lock();
+                    ui.add_enabled(should_start, egui::Checkbox::new(&mut locked.sdl_window, "Open a separate SDL window"));
+                }
+
+                ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
+
                 // <start stop button>
                 if ui.button(if should_start {"Start Emulator"} else {"Stop Emulator"}).clicked() {
                     if should_start{
-                        self.emulator_interface.start(&ctx, &self.ui_states.keymap);
+                        self.emulator_interface.start(ctx, &self.ui_states.rom_path, &self.ui_states.keymap);
+                        self.ui_states.sprite_edits.clear();
+                        self.ui_states.edited_rom_path.clone_from(&self.ui_states.rom_path);
                     }else{
                         self.emulator_interface.kill();
                     }
                 }
                 // </start stop button>
                 ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
-                ui.checkbox(&mut self.emulator_interface.inter_thread.lock().freeze, "Freeze");
+                ui.horizontal(|ui| {
+                    let locked = &mut *self.emulator_interface.inter_thread.lock();
+                    ui.checkbox(&mut locked.freeze, "Freeze");
+                    if ui.add_enabled(locked.freeze && !should_start, egui::Button::new("Advance frame (F4)")).clicked() {
+                        locked.frame_advance = true;
+                    }
+                });
+
+                // <speed>
+                {
+                    let locked = &mut *self.emulator_interface.inter_thread.lock();
+                    ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
+                    ui.checkbox(&mut locked.vip_timing, "COSMAC VIP timing");
+                    ui.add_enabled(!locked.vip_timing, egui::Slider::new(&mut locked.instructions_per_frame, 1..=1000)
+                        .logarithmic(true)
+                        .text("instructions/frame"));
+                    if locked.vip_timing {
+                        ui.label(format!("{} machine cycles per frame for the interpreter", vip_timing::INTERPRETER_CYCLES_PER_FRAME));
+                    }else{
+                        ui.label(format!("{} instructions per second", locked.instructions_per_frame * emulator::TIMER_FREQUENCY));
+                    }
+                    if !should_start {
+                        ui.label(format!("Measured: {:.0} instructions/s, {:.1} frames/s", locked.measured_ips, locked.measured_fps));
+                    }
+                    ui.checkbox(&mut locked.fast_forward, "Fast forward (hold Tab)");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut locked.slow_motion, "Slow motion (F3)");
+                        ui.add_enabled(locked.slow_motion, egui::Slider::new(&mut locked.slow_motion_factor, 0.05..=1.0).text("speed"));
+                    });
+                }
+                // </speed>
+
+                // <capture>
+                {
+                    let locked = &mut *self.emulator_interface.inter_thread.lock();
+                    ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(!should_start, egui::Button::new("Screenshot (F12, Ctrl+S)")).clicked() {
+                            locked.screenshot_requested = true;
+                        }
+                        egui::ComboBox::from_id_source("screenshot_format")
+                            .selected_text(locked.screenshot_format.name())
+                            .show_ui(ui, |ui| {
+                                for format in ImageFormat::ALL {
+                                    ui.selectable_value(&mut locked.screenshot_format, format, format.name());
+                                }
+                            });
+                        ui.add(egui::DragValue::new(&mut locked.capture_scale).clamp_range(1..=postprocess::MAX_SCALE).suffix("x"));
+                    });
+                    ui.horizontal(|ui| {
+                        let label = if locked.recording {"Stop recording (F9, Ctrl+R)"} else {"Record (F9, Ctrl+R)"};
+                        if ui.add_enabled(!should_start, egui::Button::new(label)).clicked() {
+                            locked.recording_requested = true;
+                        }
+                        ui.add_enabled_ui(!locked.recording, |ui| {
+                            egui::ComboBox::from_id_source("recording_format")
+                                .selected_text(locked.recording_format.name())
+                                .show_ui(ui, |ui| {
+                                    for format in VideoFormat::ALL {
+                                        ui.selectable_value(&mut locked.recording_format, format, format.name());
+                                    }
+                                });
+                            ui.checkbox(&mut locked.record_audio, "WAV audio");
+                        });
+                    });
+                    if !locked.capture_status.is_empty() {
+                        ui.label(&locked.capture_status);
+                    }
+                }
+                // </capture>
 
                 ui.allocate_space(egui::vec2(60f32, 10f32)); // padding
                 ui.allocate_space(ui.available_size());
@@ -357,6 +895,13 @@ impl eframe::App for EmulatorUI {
                             ui.colored_label(internals_color, "Sound timer: ");
                             ui.label(format!("{:03}", internals.sound_timer));
                         });
+                        ui.horizontal(|ui|{
+                            ui.colored_label(internals_color, "FX0A: ");
+                            match &internals.key_wait {
+                                Some(key_wait) => ui.label(key_wait.description()),
+                                None => ui.label("Not waiting"),
+                            };
+                        });
                     });
                 });
 
@@ -372,6 +917,9 @@ impl eframe::App for EmulatorUI {
             .show(ctx, |ui| {
                 let locked = self.emulator_interface.inter_thread.lock();
                 let internals = &locked.internal_state;
+                // the last 16 lines of memory are the furthest the window can scroll
+                let max_start = internals.memory.len().saturating_sub(16*16) as i32;
+                self.ui_states.memory_start = self.ui_states.memory_start.clamp(0, max_start);
                 ui.horizontal(|ui| {
                     ui.vertical(|ui| {
                         egui::Grid::new("Memory_Grid")
@@ -381,9 +929,9 @@ impl eframe::App for EmulatorUI {
                             .show(ui, |ui| {
                                 ui.monospace("       +0 +1 +2 +3 +4 +5 +6 +7 +8 +9 +A +B +C +D +E +F");
                                 ui.end_row();
-                                let start_point = 3840 - self.ui_states.memory_slider;
+                                let start_point = self.ui_states.memory_start;
                                 let mut line: String = format!("{:04X}: ", start_point);
-                                let mem_area = &internals.memory[start_point as usize..(start_point + 16*16) as usize];
+                                let mem_area = &internals.memory[start_point as usize..(start_point as usize + 16*16).min(internals.memory.len())];
                                 for (i, byte) in mem_area.iter().enumerate() {
                                     if i % 16 == 0 && i != 0{
                                         ui.monospace(&mut line);
@@ -400,13 +948,16 @@ impl eframe::App for EmulatorUI {
                         style.spacing.slider_width = 330f32;
                         ctx.set_style(style);
 
+                        // the slider's top is its maximum, so it runs from the end of memory down to the start
+                        let mut slider = max_start - self.ui_states.memory_start;
                         ui.add_sized(
                             ui.available_size(),
-                            egui::Slider::new(&mut self.ui_states.memory_slider, 0..=3840 )
+                            egui::Slider::new(&mut slider, 0..=max_start)
                             .vertical()
                             .show_value(false)
                             .step_by(16f64),
                         );
+                        self.ui_states.memory_start = max_start - slider;
                         ui.allocate_space(egui::Vec2::new(0.0, ui.available_height()));
                     });
                     
@@ -421,8 +972,7 @@ impl eframe::App for EmulatorUI {
                         for event in events.iter() {
                             if let egui::Event::Scroll(scroll) = event {
                                 let direction = (scroll[1] / scroll[1].abs()) as i32; 
-                                self.ui_states.memory_slider += direction * 16;
-                                self.ui_states.memory_slider = self.ui_states.memory_slider.clamp(0, 3840);
+                                self.ui_states.memory_start -= direction * 16;
                             }
                         }
                     }
@@ -476,5 +1026,877 @@ impl eframe::App for EmulatorUI {
             }
         });
         // </keybinds>
+
+        // <coverage>
+        egui::Window::new("Coverage")
+            .open(&mut self.window_states.coverage)
+            .default_size([500.0, 500.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let mut locked = self.emulator_interface.inter_thread.lock();
+
+                let (executed, total) = locked.coverage.summary();
+                ui.label(format!("Executed {}/{} instructions ({:.2}%)", executed, total, locked.coverage.percentage()));
+
+                ui.horizontal(|ui| {
+                    ui.label("Listing: ");
+                    ui.text_edit_singleline(&mut self.ui_states.listing_path);
+                    if ui.button("Export").clicked() {
+                        let result = locked.coverage.write_listing(&self.ui_states.listing_path, &locked.internal_state.memory);
+                        self.ui_states.listing_status = match result {
+                            Ok(_) => format!("Written to {}", self.ui_states.listing_path),
+                            Err(e) => format!("Export failed: {}", e),
+                        };
+                    }
+                    if ui.button("Reset").clicked() {
+                        locked.coverage.reset();
+                    }
+                });
+                ui.label(&self.ui_states.listing_status);
+
+                ui.separator();
+
+                egui::containers::ScrollArea::new([true, true])
+                    .show(ui, |ui| {
+                        for line in locked.coverage.listing(&locked.internal_state.memory).lines() {
+                            if line.trim_start().starts_with("#####") {
+                                ui.colored_label(egui::Color32::LIGHT_RED, egui::RichText::new(line).monospace());
+                            }else{
+                                ui.monospace(line);
+                            }
+                        }
+                    });
+            });
+        // </coverage>
+
+        // <profiler>
+        egui::Window::new("Profiler")
+            .open(&mut self.window_states.profiler)
+            .default_size([500.0, 500.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let mut locked = self.emulator_interface.inter_thread.lock();
+
+                ui.horizontal(|ui| {
+                    ui.label("Folded stacks: ");
+                    ui.text_edit_singleline(&mut self.ui_states.profile_path);
+                    if ui.button("Export").clicked() {
+                        self.ui_states.profile_status = match locked.profiler.write_folded_stacks(&self.ui_states.profile_path) {
+                            Ok(_) => format!("Written to {}", self.ui_states.profile_path),
+                            Err(e) => format!("Export failed: {}", e),
+                        };
+                    }
+                    if ui.button("Reset").clicked() {
+                        locked.profiler = Profiler::new(locked.internal_state.config.program_start);
+                    }
+                });
+                ui.label(&self.ui_states.profile_status);
+
+                ui.separator();
+
+                let total_cycles = locked.profiler.total_cycles().max(1) as f64;
+                let routines: Vec<_> = locked.profiler.routines(self.ui_states.profiler_sort, self.ui_states.profiler_descending)
+                    .into_iter()
+                    .map(|routine| (locked.profiler.routine_name(routine.address), routine))
+                    .collect();
+                drop(locked);
+
+                egui::containers::ScrollArea::new([true, true])
+                    .show(ui, |ui| {
+                        egui::Grid::new("Profiler_Grid")
+                            .num_columns(4)
+                            .spacing([20.0, 4.0])
+                            .striped(true)
+                            .show(ui, |ui| {
+                                let columns = [
+                                    ("Subroutine", ProfilerSort::Address),
+                                    ("Calls", ProfilerSort::Calls),
+                                    ("Self", ProfilerSort::SelfCycles),
+                                    ("Inclusive", ProfilerSort::InclusiveCycles),
+                                ];
+                                for (name, sort) in columns {
+                                    let name = if self.ui_states.profiler_sort != sort {
+                                        name.to_owned()
+                                    }else if self.ui_states.profiler_descending {
+                                        format!("{} v", name)
+                                    }else{
+                                        format!("{} ^", name)
+                                    };
+                                    if ui.button(name).clicked() {
+                                        if self.ui_states.profiler_sort == sort {
+                                            self.ui_states.profiler_descending = !self.ui_states.profiler_descending;
+                                        }else{
+                                            self.ui_states.profiler_sort = sort;
+                                            self.ui_states.profiler_descending = sort != ProfilerSort::Address;
+                                        }
+                                    }
+                                }
+                                ui.end_row();
+
+                                for (name, routine) in routines.iter() {
+                                    ui.monospace(name);
+                                    ui.monospace(routine.calls.to_string());
+                                    ui.monospace(format!("{} ({:.1}%)", routine.self_cycles, routine.self_cycles as f64 / total_cycles * 100f64));
+                                    ui.monospace(format!("{} ({:.1}%)", routine.inclusive_cycles, routine.inclusive_cycles as f64 / total_cycles * 100f64));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        // </profiler>
+
+        // <disassembly>
+        egui::Window::new("Disassembly")
+            .open(&mut self.window_states.disassembly)
+            .default_size([500.0, 500.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let mut locked = self.emulator_interface.inter_thread.lock();
+
+                ui.horizontal(|ui| {
+                    ui.label("Graph: ");
+                    ui.text_edit_singleline(&mut self.ui_states.dot_path);
+                    if ui.button("Export DOT").clicked() {
+                        self.ui_states.dot_status = match locked.analysis.write_dot(&self.ui_states.dot_path, &locked.internal_state.memory) {
+                            Ok(_) => format!("Written to {}", self.ui_states.dot_path),
+                            Err(e) => format!("Export failed: {}", e),
+                        };
+                    }
+                    if ui.button("Reanalyze").clicked() {
+                        let analysis = &locked.analysis;
+                        locked.analysis = Analysis::analyze(&locked.internal_state.memory, analysis.program_start, analysis.program_len);
+                    }
+                });
+                ui.label(&self.ui_states.dot_status);
+                ui.checkbox(&mut self.ui_states.follow_pc, "Follow PC");
+
+                ui.label(format!("{} blocks, {} computed jumps, {} writes into code",
+                    locked.analysis.blocks.len(), locked.analysis.computed_jumps.len(), locked.analysis.self_modifying_writes.len()));
+
+                ui.separator();
+
+                let pc = locked.internal_state.PC;
+                // the instruction that last drew the pixel picked in the display
+                let inspected = self.ui_states.inspected_pixel.and_then(|pixel| locked.internal_state.draw_records[pixel]).map(|record| record.pc);
+                let target = self.ui_states.disassembly_target.take();
+                egui::containers::ScrollArea::new([true, true])
+                    .show(ui, |ui| {
+                        for line in locked.analysis.disassembly(&locked.internal_state.memory) {
+                            match line {
+                                DisassemblyLine::Label(name) => {
+                                    ui.colored_label(egui::Color32::LIGHT_BLUE, egui::RichText::new(format!("{}:", name)).monospace());
+                                },
+                                DisassemblyLine::Instruction { address, opcode, mnemonic, note } => {
+                                    let mut text = format!("    {:03X}: {:04X}  {}", address, opcode, mnemonic);
+                                    let flagged = note.is_some();
+                                    if let Some(note) = note {
+                                        text.push_str(&format!("  ; {}", note));
+                                    }
+                                    let response = if address == pc {
+                                        ui.colored_label(egui::Color32::LIGHT_GREEN, egui::RichText::new(text).monospace())
+                                    }else if Some(address) == inspected {
+                                        ui.colored_label(egui::Color32::YELLOW, egui::RichText::new(text).monospace())
+                                    }else if flagged {
+                                        ui.colored_label(egui::Color32::LIGHT_RED, egui::RichText::new(text).monospace())
+                                    }else{
+                                        ui.monospace(text)
+                                    };
+                                    if address == pc && self.ui_states.follow_pc || Some(address) == target {
+                                        response.scroll_to_me(Some(egui::Align::Center));
+                                    }
+                                },
+                                DisassemblyLine::Data { address, bytes } => {
+                                    let bytes: Vec<String> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                                    let response = ui.colored_label(egui::Color32::GRAY, egui::RichText::new(format!("    {:03X}: DB {}", address, bytes.join(", "))).monospace());
+                                    // code the analysis didn't reach shows up as data
+                                    if target.is_some_and(|target| (address..address + bytes.len() as u16).contains(&target)) {
+                                        response.scroll_to_me(Some(egui::Align::Center));
+                                    }
+                                },
+                            }
+                        }
+                    });
+            });
+        // </disassembly>
+
+        // <platform>
+        egui::Window::new("Platform")
+            .open(&mut self.window_states.platform)
+            .default_size([400.0, 300.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+
+                let mut platform = locked.platform;
+                egui::ComboBox::from_label("Platform")
+                    .selected_text(platform.name())
+                    .show_ui(ui, |ui| {
+                        for option in Platform::ALL {
+                            ui.selectable_value(&mut platform, option, option.name());
+                        }
+                    });
+                if platform != locked.platform {
+                    locked.platform = platform;
+                    locked.quirks = Quirks::preset(platform);
+                }
+
+                ui.allocate_space(egui::vec2(0f32, 5f32)); // padding
+
+                ui.checkbox(&mut locked.quirks.vf_reset, "VF reset by 8XY1, 8XY2 and 8XY3");
+                ui.checkbox(&mut locked.quirks.memory_increment, "FX55 and FX65 increment I");
+                ui.checkbox(&mut locked.quirks.shifting, "8XY6 and 8XYE shift VX in place");
+                ui.checkbox(&mut locked.quirks.jumping, "BNNN jumps to NNN + VX");
+                ui.checkbox(&mut locked.quirks.clipping, "Sprites clip at screen edges");
+                ui.checkbox(&mut locked.quirks.display_wait, "DXYN waits for the next frame");
+                ui.checkbox(&mut locked.quirks.key_release, "FX0A waits for the key to be released");
+                if ui.button("Reset to emulator defaults").clicked() {
+                    locked.quirks = Quirks::default();
+                }
+
+                // <memory layout>
+                ui.separator();
+                ui.label("Memory layout, used when the next ROM is loaded:");
+                let running = self.emulator_interface.status();
+                ui.add_enabled_ui(!running, |ui| {
+                    let config = &mut locked.machine_config;
+                    egui::ComboBox::from_label("Machine")
+                        .selected_text(MachineConfig::PRESETS.iter().find(|(_, preset)| preset == config).map_or("Custom", |(name, _)| name))
+                        .show_ui(ui, |ui| {
+                            for (name, preset) in MachineConfig::PRESETS {
+                                ui.selectable_value(config, preset, name);
+                            }
+                        });
+                    egui::ComboBox::from_label("Memory")
+                        .selected_text(config.memory_size.name())
+                        .show_ui(ui, |ui| {
+                            for size in MemorySize::ALL {
+                                ui.selectable_value(&mut config.memory_size, size, size.name());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        let max_start = (config.memory_len() - 2) as u16;
+                        config.program_start = config.program_start.min(max_start);
+                        ui.add(egui::DragValue::new(&mut config.program_start).clamp_range(0..=max_start));
+                        ui.label(format!("Program start (0x{:03X})", config.program_start));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut config.stack_depth).clamp_range(1..=64));
+                        ui.label("Stack depth");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut config.fill).clamp_range(0..=255));
+                        ui.label(format!("Initial memory fill (0x{:02X})", config.fill));
+                    });
+                    if ui.button("Reset layout to emulator defaults").clicked() {
+                        *config = MachineConfig::default();
+                    }
+                });
+                // </memory layout>
+
+                ui.separator();
+
+                ui.checkbox(&mut locked.apply_detection, "Apply suggestion when a ROM is loaded");
+                match locked.detection.clone() {
+                    Some(detection) => {
+                        ui.horizontal(|ui| {
+                            ui.label("Suggested: ");
+                            ui.colored_label(egui::Color32::LIGHT_GREEN, detection.platform.name());
+                            let applied = locked.platform == detection.platform && locked.quirks == detection.quirks;
+                            if ui.add_enabled(!applied, egui::Button::new("Apply")).clicked() {
+                                locked.platform = detection.platform;
+                                locked.quirks = detection.quirks;
+                            }
+                        });
+                        for reason in detection.reasons.iter() {
+                            ui.label(format!("- {}", reason));
+                        }
+                    },
+                    None => {
+                        ui.label("Start the emulator to get a suggestion for the loaded ROM");
+                    },
+                }
+            });
+        // </platform>
+
+        // <database>
+        egui::Window::new("Database")
+            .open(&mut self.window_states.database)
+            .default_size([400.0, 300.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+
+                ui.label(format!("{} known ROMs", locked.database.len()));
+                ui.label("The built-in database only knows the bundled ROMs, import the community programs.json for settings of other ROMs");
+                ui.checkbox(&mut locked.apply_database, "Apply database settings when a ROM is loaded");
+
+                ui.horizontal(|ui| {
+                    ui.label("Import: ");
+                    ui.text_edit_singleline(&mut self.ui_states.database_path);
+                    if ui.button("Import").clicked() {
+                        self.ui_states.database_status = match Database::import(&self.ui_states.database_path) {
+                            Ok(database) => {
+                                let status = format!("Imported {} ROMs", database.len());
+                                locked.database = database;
+                                status
+                            },
+                            Err(e) => format!("Import failed: {}", e),
+                        };
+                    }
+                });
+                ui.label(&self.ui_states.database_status);
+
+                ui.separator();
+
+                match &locked.rom_entry {
+                    Some(entry) => {
+                        ui.heading(&entry.title);
+                        if !entry.authors.is_empty() {
+                            ui.label(format!("by {}", entry.authors.join(", ")));
+                        }
+                        if !entry.description.is_empty() {
+                            ui.label(&entry.description);
+                        }
+                        ui.label(format!("Platform: {} ({})", entry.platform.name(), entry.platform_name));
+                        if let Some(tickrate) = entry.tickrate {
+                            ui.label(format!("Tick rate: {} instructions per frame", tickrate));
+                        }
+                        ui.monospace(format!("SHA-1: {}", entry.sha1));
+                    },
+                    None => {
+                        ui.label("The loaded ROM is not in the database");
+                    },
+                }
+            });
+        // </database>
+
+        // <machine code>
+        egui::Window::new("Machine Code")
+            .open(&mut self.window_states.machine_code)
+            .default_size([350.0, 250.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+
+                ui.checkbox(&mut locked.machine_code.trap_unhandled, "Stop with a fault on calls without a routine");
+                ui.separator();
+
+                let mut targets: Vec<u16> = locked.unhandled_machine_code.keys().copied().collect();
+                for target in targets.iter() {
+                    if locked.machine_code.get(*target).is_some() {
+                        locked.unhandled_machine_code.remove(target);
+                    }
+                }
+                targets.retain(|target| locked.unhandled_machine_code.contains_key(target));
+
+                if targets.is_empty() {
+                    ui.label("No unhandled 0NNN calls");
+                }
+                egui::Grid::new("machine_code_grid").striped(true).show(ui, |ui| {
+                    for target in targets.iter() {
+                        ui.colored_label(egui::Color32::YELLOW, format!("0x{:03X}", target));
+                        ui.label(format!("first called from 0x{:03X}", locked.unhandled_machine_code[target]));
+                        egui::ComboBox::from_id_source(("machine_code_routine", *target))
+                            .selected_text("Unhandled")
+                            .show_ui(ui, |ui| {
+                                for (name, routine) in machine_code::BUILT_IN_ROUTINES {
+                                    if ui.selectable_label(false, name).clicked() {
+                                        locked.machine_code.register(*target, name, routine);
+                                    }
+                                }
+                            });
+                        ui.end_row();
+                    }
+
+                    let handled: Vec<(u16, String)> = locked.machine_code.routines()
+                        .map(|(address, name)| (address, name.to_owned()))
+                        .collect();
+                    for (address, name) in handled {
+                        ui.label(format!("0x{:03X}", address));
+                        ui.label(name);
+                        if ui.button("Remove").clicked() {
+                            locked.machine_code.unregister(address);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        // </machine code>
+
+        // <font editor>
+        egui::Window::new("Font Editor")
+            .open(&mut self.window_states.font_editor)
+            .default_size([300.0, 350.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+
+                egui::ComboBox::from_label("Font")
+                    .selected_text(locked.font_set.name())
+                    .show_ui(ui, |ui| {
+                        for font_set in FontSet::ALL {
+                            if ui.selectable_label(locked.font_set == font_set, font_set.name()).clicked() && locked.font_set != font_set {
+                                // picking Custom keeps the current glyphs so they can be edited
+                                if font_set != FontSet::Custom {
+                                    locked.font = Font::preset(font_set);
+                                }
+                                locked.font_set = font_set;
+                                locked.font_changed = true;
+                            }
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("Address: ");
+                    let max_address = (locked.machine_config.memory_len() - locked.font.len()) as u16;
+                    if ui.add(egui::DragValue::new(&mut locked.font_address).clamp_range(0..=max_address)).changed() {
+                        locked.font_changed = true;
+                    }
+                    ui.label(format!("0x{:03X}", locked.font_address));
+                });
+
+                ui.separator();
+
+                ui.horizontal_wrapped(|ui| {
+                    for digit in 0..16 {
+                        ui.selectable_value(&mut self.ui_states.font_glyph, digit, format!("{:X}", digit));
+                    }
+                });
+                ui.add_enabled(!locked.font.large.is_empty(), egui::Checkbox::new(&mut self.ui_states.font_large, "Large glyph (FX30)"));
+                let large = self.ui_states.font_large && !locked.font.large.is_empty();
+
+                // <glyph pixels>
+                match locked.font.glyph_mut(self.ui_states.font_glyph, large) {
+                    Some(rows) => {
+                        let mut edited = false;
+                        ui.spacing_mut().item_spacing = egui::vec2(0f32, 0f32);
+                        for row in rows.iter_mut() {
+                            ui.horizontal(|ui| {
+                                for bit in (0..8).rev() {
+                                    let (rect, response) = ui.allocate_exact_size(egui::vec2(18f32, 18f32), egui::Sense::click());
+                                    let color = if *row >> bit & 1 == 1 {egui::Color32::WHITE} else {egui::Color32::from_gray(40)};
+                                    ui.painter().rect_filled(rect.shrink(1f32), 0f32, color);
+                                    if response.clicked() {
+                                        *row ^= 1 << bit;
+                                        edited = true;
+                                    }
+                                }
+                            });
+                        }
+                        if edited {
+                            locked.font_set = FontSet::Custom;
+                            locked.font_changed = true;
+                        }
+                    },
+                    None => {
+                        ui.label("The font has no large glyph for this digit");
+                    },
+                }
+                // </glyph pixels>
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("File: ");
+                    ui.text_edit_singleline(&mut self.ui_states.font_path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Load").clicked() {
+                        self.ui_states.font_status = match Font::load(&self.ui_states.font_path) {
+                            Ok(font) => {
+                                locked.font = font;
+                                locked.font_set = FontSet::Custom;
+                                locked.font_changed = true;
+                                format!("Loaded {}", self.ui_states.font_path)
+                            },
+                            Err(e) => format!("Load failed: {}", e),
+                        };
+                    }
+                    if ui.button("Save").clicked() {
+                        self.ui_states.font_status = match locked.font.save(&self.ui_states.font_path) {
+                            Ok(()) => format!("Saved {}", self.ui_states.font_path),
+                            Err(e) => format!("Save failed: {}", e),
+                        };
+                    }
+                });
+                ui.label(&self.ui_states.font_status);
+            });
+        // </font editor>
+
+        // <sprite viewer>
+        egui::Window::new("Sprite Viewer")
+            .open(&mut self.window_states.sprite_viewer)
+            .default_size([560.0, 400.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = self.emulator_interface.inter_thread.lock();
+                let internals = &locked.internal_state;
+                let mut sheet = self.ui_states.sprite_sheet;
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.ui_states.sprite_follow_i, "Follow I");
+                    ui.label("Address: ");
+                    let max_address = internals.memory.len().saturating_sub(1) as u16;
+                    ui.add_enabled(!self.ui_states.sprite_follow_i, egui::DragValue::new(&mut sheet.address).clamp_range(0..=max_address));
+                    ui.label(format!("0x{:03X}", sheet.address));
+                });
+                if self.ui_states.sprite_follow_i {
+                    sheet.address = internals.I;
+                }
+                ui.horizontal(|ui| {
+                    for size in SpriteSize::ALL {
+                        ui.selectable_value(&mut sheet.size, size, size.name());
+                    }
+                    ui.label("Height: ");
+                    ui.add_enabled(sheet.size == SpriteSize::Small, egui::DragValue::new(&mut sheet.height).clamp_range(1..=15));
+                    ui.label("Sprites: ");
+                    ui.add(egui::DragValue::new(&mut sheet.count).clamp_range(1..=256));
+                });
+                // the selection is kept as positions in the sheet, which point at other sprites once the sheet moves
+                let old_sheet = self.ui_states.sprite_sheet;
+                if sheet.address != old_sheet.address || sheet.size != old_sheet.size || sheet.height != old_sheet.height {
+                    self.ui_states.selected_sprites.clear();
+                }
+                self.ui_states.selected_sprites.retain(|&index| index < sheet.count);
+                self.ui_states.sprite_sheet = sheet;
+
+                let next_draw = sprites::next_draw(internals);
+                ui.label(match &next_draw {
+                    Some(range) => format!("The next DXYN draws 0x{:03X}-0x{:03X}", range.start, range.end - 1),
+                    None => "The next instruction doesn't draw".to_owned(),
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("File: ");
+                    ui.text_edit_singleline(&mut self.ui_states.sprite_path);
+                });
+                ui.horizontal(|ui| {
+                    let export_selected = ui.add_enabled(!self.ui_states.selected_sprites.is_empty(), egui::Button::new("Export selected")).clicked();
+                    let export_sheet = ui.button("Export sheet").clicked();
+                    if export_selected || export_sheet {
+                        let exported: Vec<usize> = if export_selected {
+                            self.ui_states.selected_sprites.iter().copied().collect()
+                        }else{
+                            (0..sheet.count).collect()
+                        };
+                        self.ui_states.sprite_status = match sheet.export_png(&self.ui_states.sprite_path, &internals.memory, &exported, &locked.palette) {
+                            Ok(()) => format!("Exported {} sprites to {}", exported.len(), self.ui_states.sprite_path),
+                            Err(e) => format!("Export failed: {}", e),
+                        };
+                    }
+                    if ui.button("Clear selection").clicked() {
+                        self.ui_states.selected_sprites.clear();
+                    }
+                    let first = self.ui_states.selected_sprites.iter().next().copied();
+                    if ui.add_enabled(first.is_some(), egui::Button::new("Edit")).on_hover_text("Opens the first selected sprite in the sprite editor").clicked() {
+                        if let Some(first) = first {
+                            self.ui_states.editor_sheet = SpriteSheet { address: sheet.sprite_address(first) as u16, count: 1, ..sheet };
+                            self.window_states.sprite_editor = true;
+                        }
+                    }
+                });
+                ui.label(&self.ui_states.sprite_status);
+
+                ui.separator();
+
+                // <sprite sheet>
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let pixel_size = EmulatorUI::SPRITE_PIXEL_SIZE;
+                    let sprite_size = egui::vec2(sheet.width() as f32 * pixel_size, sheet.rows() as f32 * pixel_size);
+                    for first in (0..sheet.count).step_by(sprites::SHEET_COLUMNS) {
+                        ui.horizontal(|ui| {
+                            for index in first..(first + sprites::SHEET_COLUMNS).min(sheet.count) {
+                                let (rect, response) = ui.allocate_exact_size(sprite_size, egui::Sense::click());
+                                for y in 0..sheet.rows() {
+                                    for x in 0..sheet.width() {
+                                        // rows the next DXYN draws are shown in yellow
+                                        let drawn_next = next_draw.as_ref().is_some_and(|range| range.contains(&sheet.byte_address(index, x, y)));
+                                        let color = match (sheet.pixel(&internals.memory, index, x, y), drawn_next) {
+                                            (true, false) => egui::Color32::WHITE,
+                                            (false, false) => egui::Color32::from_gray(40),
+                                            (true, true) => egui::Color32::YELLOW,
+                                            (false, true) => egui::Color32::from_rgb(90, 80, 0),
+                                        };
+                                        let min = rect.min + egui::vec2(x as f32 * pixel_size, y as f32 * pixel_size);
+                                        ui.painter().rect_filled(egui::Rect::from_min_size(min, egui::vec2(pixel_size, pixel_size)), 0f32, color);
+                                    }
+                                }
+                                if self.ui_states.selected_sprites.contains(&index) {
+                                    ui.painter().rect_stroke(rect.expand(1f32), 0f32, egui::Stroke::new(2f32, egui::Color32::LIGHT_BLUE));
+                                }
+                                if response.on_hover_text(format!("0x{:03X}", sheet.sprite_address(index))).clicked() && !self.ui_states.selected_sprites.remove(&index) {
+                                    self.ui_states.selected_sprites.insert(index);
+                                }
+                            }
+                        });
+                    }
+                });
+                // </sprite sheet>
+            });
+        // </sprite viewer>
+
+        // <sprite editor>
+        egui::Window::new("Sprite Editor")
+            .open(&mut self.window_states.sprite_editor)
+            .default_size([300.0, 450.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+                let sheet = &mut self.ui_states.editor_sheet;
+
+                ui.horizontal(|ui| {
+                    ui.label("Address: ");
+                    let max_address = locked.internal_state.memory.len().saturating_sub(1) as u16;
+                    ui.add(egui::DragValue::new(&mut sheet.address).clamp_range(0..=max_address));
+                    ui.label(format!("0x{:03X}", sheet.address));
+                });
+                ui.horizontal(|ui| {
+                    for size in SpriteSize::ALL {
+                        ui.selectable_value(&mut sheet.size, size, size.name());
+                    }
+                    // bitmaps can be taller than a single DXYN draws
+                    ui.label("Height: ");
+                    ui.add_enabled(sheet.size == SpriteSize::Small, egui::DragValue::new(&mut sheet.height).clamp_range(1..=32));
+                });
+
+                ui.separator();
+
+                // <bitmap pixels>
+                // clicked pixels are flipped in the copy the ui shows right away and in the emulator's memory with the next frame
+                ui.vertical(|ui| {
+                    ui.spacing_mut().item_spacing = egui::vec2(0f32, 0f32);
+                    for y in 0..sheet.rows() {
+                        ui.horizontal(|ui| {
+                            for x in 0..sheet.width() {
+                                let (rect, response) = ui.allocate_exact_size(egui::vec2(18f32, 18f32), egui::Sense::click());
+                                let color = if sheet.pixel(&locked.internal_state.memory, 0, x, y) {egui::Color32::WHITE} else {egui::Color32::from_gray(40)};
+                                ui.painter().rect_filled(rect.shrink(1f32), 0f32, color);
+                                let address = sheet.byte_address(0, x, y);
+                                if response.clicked() {
+                                    if let Some(byte) = locked.internal_state.memory.get_mut(address) {
+                                        *byte ^= 0b10000000 >> (x % 8);
+                                        locked.memory_writes.push((address as u16, *byte));
+                                        self.ui_states.sprite_edits.insert(address as u16, *byte);
+                                    }
+                                }
+                            }
+                        });
+                    }
+                });
+                // </bitmap pixels>
+
+                ui.separator();
+
+                ui.label(format!("{} bytes edited since the rom was started", self.ui_states.sprite_edits.len()));
+                ui.horizontal(|ui| {
+                    ui.label("Patched rom: ");
+                    ui.text_edit_singleline(&mut self.ui_states.patch_path);
+                });
+                ui.horizontal(|ui| {
+                    let edited = !self.ui_states.sprite_edits.is_empty();
+                    if ui.add_enabled(edited, egui::Button::new("Save patched rom")).clicked() {
+                        let program_start = locked.internal_state.config.program_start;
+                        self.ui_states.patch_status = match sprites::write_patched_rom(&self.ui_states.edited_rom_path, &self.ui_states.patch_path, program_start, &self.ui_states.sprite_edits) {
+                            Ok(0) => format!("Saved {}", self.ui_states.patch_path),
+                            Ok(skipped) => format!("Saved {}, {} edits outside of the rom were left out", self.ui_states.patch_path, skipped),
+                            Err(e) => format!("Save failed: {}", e),
+                        };
+                    }
+                    // memory stays as it is, only the patch forgets about the edits
+                    if ui.add_enabled(edited, egui::Button::new("Forget edits")).clicked() {
+                        self.ui_states.sprite_edits.clear();
+                    }
+                });
+                ui.label(&self.ui_states.patch_status);
+            });
+        // </sprite editor>
+
+        // <palette>
+        egui::Window::new("Palette")
+            .open(&mut self.window_states.palette)
+            .default_size([250.0, 200.0])
+            .resizable(false)
+            .show(ctx, |ui| {
+                let locked = &mut *self.emulator_interface.inter_thread.lock();
+
+                egui::ComboBox::from_label("Palette")
+                    .selected_text(locked.palette_set.name())
+                    .show_ui(ui, |ui| {
+                        for palette_set in PaletteSet::ALL {
+                            if ui.selectable_label(locked.palette_set == palette_set, palette_set.name()).clicked() && locked.palette_set != palette_set {
+                                // picking Custom keeps the current colours so they can be edited
+                                if palette_set != PaletteSet::Custom {
+                                    locked.palette = Palette::preset(palette_set);
+                                }
+                                locked.palette_set = palette_set;
+                            }
+                        }
+                    });
+
+                ui.separator();
+
+                for (color, name) in locked.palette.colors.iter_mut().zip(palette::COLOR_NAMES) {
+                    ui.horizontal(|ui| {
+                        if ui.color_edit_button_srgb(color).changed() {
+                            locked.palette_set = PaletteSet::Custom;
+                        }
+                        ui.label(name);
+                    });
+                }
+                ui.label("Plane 2 and both planes are unused for now, only a single plane is emulated");
+
+                ui.separator();
+
+                // <rom palette>
+                let has_rom = !locked.rom_hash.is_empty();
+                let has_override = locked.palette_overrides.get(&locked.rom_hash).is_some();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(has_rom, egui::Button::new("Use for this ROM")).clicked() {
+                        self.ui_states.palette_status = match locked.palette_overrides.set(&locked.rom_hash, locked.palette) {
+                            Ok(()) => "The palette is used whenever this ROM is loaded".to_owned(),
+                            Err(e) => format!("Saving failed: {}", e),
+                        };
+                    }
+                    if ui.add_enabled(has_override, egui::Button::new("Forget for this ROM")).clicked() {
+                        self.ui_states.palette_status = match locked.palette_overrides.remove(&locked.rom_hash) {
+                            Ok(()) => "The ROM uses the selected palette again".to_owned(),
+                            Err(e) => format!("Saving failed: {}", e),
+                        };
+                    }
+                });
+                ui.label(&self.ui_states.palette_status);
+                // </rom palette>
+            });
+        // </palette>
+
+        // <display>
+        let texture = self.update_display_texture(ctx);
+        egui::Window::new("Display")
+            .open(&mut self.window_states.display)
+            .default_pos(egui::pos2(300f32, 40f32))
+            .default_size([512.0, 256.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let mut settings = self.emulator_interface.inter_thread.lock().display_settings;
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("scale_mode")
+                        .selected_text(settings.scale_mode.name())
+                        .show_ui(ui, |ui| {
+                            for scale_mode in ScaleMode::ALL {
+                                ui.selectable_value(&mut settings.scale_mode, scale_mode, scale_mode.name());
+                            }
+                        });
+                    ui.checkbox(&mut settings.fullscreen, "Fullscreen").on_hover_text("Escape leaves fullscreen, F11 toggles it in the SDL window");
+                });
+                // <post-processing>
+                ui.collapsing("Post-processing", |ui| {
+                    let post_processing = &mut settings.post_processing;
+                    ui.checkbox(&mut post_processing.blend_frames, "Blend every frame with the previous one");
+                    ui.horizontal(|ui| {
+                        ui.label("Keep cleared pixels lit for ");
+                        ui.add(egui::DragValue::new(&mut post_processing.hold_frames).clamp_range(0..=30));
+                        ui.label(" frames");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Phosphor half-life: ");
+                        ui.add(egui::DragValue::new(&mut post_processing.phosphor_half_life).clamp_range(0..=1000).suffix(" ms"));
+                    });
+                    ui.checkbox(&mut post_processing.scanlines, "Scanlines");
+                    ui.checkbox(&mut post_processing.pixel_grid, "Pixel grid");
+                });
+                // </post-processing>
+                self.emulator_interface.inter_thread.lock().display_settings = settings;
+
+                // <sprite overlay>
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.ui_states.sprite_overlay, "Sprite boxes").on_hover_text("Outlines every DXYN of the last frame, red ones set VF");
+                    ui.add_enabled(self.ui_states.sprite_overlay, egui::DragValue::new(&mut self.ui_states.overlay_fade_frames)
+                        .clamp_range(0..=emulator::DRAW_HISTORY_FRAMES - 1)
+                        .prefix("Fade over ")
+                        .suffix(" frames"));
+                });
+                // </sprite overlay>
+
+                // <draw inspector>
+                if let Some(pixel) = self.ui_states.inspected_pixel {
+                    let record = self.emulator_interface.inter_thread.lock().internal_state.draw_records[pixel];
+                    ui.horizontal(|ui| {
+                        ui.label(match record {
+                            Some(record) => format!("Pixel {}, {} was last toggled by the DXYN at 0x{:03X} with I = 0x{:03X} on cycle {}",
+                                pixel % 64, pixel / 64, record.pc, record.i, record.cycle),
+                            None => format!("Pixel {}, {} hasn't been drawn by a DXYN", pixel % 64, pixel / 64),
+                        });
+                        if ui.button("Clear").clicked() {
+                            self.ui_states.inspected_pixel = None;
+                        }
+                    });
+                }
+                // </draw inspector>
+
+                let (rect, response) = ui.allocate_exact_size(ui.available_size().max(egui::vec2(64f32, 32f32)), egui::Sense::click());
+                let screen = EmulatorUI::paint_display(ui, texture, rect, settings.scale_mode);
+                if self.ui_states.sprite_overlay {
+                    let locked = self.emulator_interface.inter_thread.lock();
+                    EmulatorUI::paint_sprite_boxes(ui, screen, &locked.recent_draws, self.ui_states.overlay_fade_frames, locked.quirks.clipping);
+                }
+                if let Some(pixel) = self.ui_states.inspected_pixel {
+                    let size = egui::vec2(screen.width() / 64f32, screen.height() / 32f32);
+                    let min = screen.min + egui::vec2((pixel % 64) as f32 * size.x, (pixel / 64) as f32 * size.y);
+                    ui.painter().rect_stroke(egui::Rect::from_min_size(min, size), 0f32, egui::Stroke::new(1f32, egui::Color32::YELLOW));
+                }
+                // clicking a pixel shows the instruction that drew it in the disassembly and its sprite in the memory window
+                if let Some(pos) = response.interact_pointer_pos().filter(|_| response.clicked()) {
+                    self.ui_states.inspected_pixel = EmulatorUI::pixel_at(screen, pos);
+                    let locked = self.emulator_interface.inter_thread.lock();
+                    if let Some(record) = self.ui_states.inspected_pixel.and_then(|pixel| locked.internal_state.draw_records[pixel]) {
+                        self.ui_states.disassembly_target = Some(record.pc);
+                        self.ui_states.follow_pc = false;
+                        self.ui_states.memory_start = (record.i & !0xF) as i32;
+                        self.window_states.disassembly = true;
+                        self.window_states.memory = true;
+                    }
+                }
+            });
+
+        if ctx.input().key_pressed(EmulatorUI::LEAVE_FULLSCREEN_KEY) {
+            self.emulator_interface.inter_thread.lock().display_settings.fullscreen = false;
+        }
+        let settings = self.emulator_interface.inter_thread.lock().display_settings;
+        if settings.fullscreen && !self.emulator_interface.inter_thread.lock().sdl_window {
+            // the display covers every other window until fullscreen is turned off again
+            egui::Area::new("fullscreen_display")
+                .fixed_pos(egui::pos2(0f32, 0f32))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    // takes up the whole screen so the windows underneath can't be clicked
+                    let (rect, _) = ui.allocate_exact_size(ctx.input().screen_rect().size(), egui::Sense::click());
+                    let screen = EmulatorUI::paint_display(ui, texture, rect, settings.scale_mode);
+                    if self.ui_states.sprite_overlay {
+                        let locked = self.emulator_interface.inter_thread.lock();
+                        EmulatorUI::paint_sprite_boxes(ui, screen, &locked.recent_draws, self.ui_states.overlay_fade_frames, locked.quirks.clipping);
+                    }
+                });
+        }
+
+        if (self.window_states.display || settings.fullscreen) && self.emulator_interface.status() && self.ui_states.listen_for_key == -1 && !ctx.wants_keyboard_input() {
+            self.route_keys(ctx);
+        }else{
+            self.release_keys();
+        }
+        // </display>
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_keys_map_to_the_same_sdl_keys() {
+        assert_eq!(UIStates::keycode_from_egui(egui::Key::ArrowUp), Keycode::Up);
+        assert_eq!(UIStates::keycode_from_egui(egui::Key::ArrowLeft), Keycode::Left);
+        assert_eq!(UIStates::keycode_from_egui(egui::Key::Enter), Keycode::Return);
+        assert_eq!(UIStates::keycode_from_egui(egui::Key::Num5), Keycode::Num5);
+        assert_eq!(UIStates::keycode_from_egui(egui::Key::Z), Keycode::Z);
+    }
+}
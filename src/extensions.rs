@@ -0,0 +1,79 @@
+use std::sync::{Arc, RwLock};
+
+use crate::disassembler;
+use crate::emulator::C8;
+
+/// Handler for opcodes the built-in instruction set leaves free, used to experiment with custom instructions
+pub trait OpcodeExtension: Send + Sync {
+    /// Returns true if the handler implements the opcode, only asked about opcodes the built-in decoder doesn't claim
+    fn claims(&self, opcode: u16) -> bool;
+
+    /// Mnemonic shown by the disassembler
+    fn mnemonic(&self, opcode: u16) -> String;
+
+    /// Executes the opcode, the program counter already points past it.
+    /// Returns the description shown in the instruction log.
+    fn execute(&self, opcode: u16, internals: &mut C8, gbuf: &mut [u8; 64*32]) -> String;
+}
+
+/// Handlers are asked in the order they were registered
+static EXTENSIONS: RwLock<Vec<Arc<dyn OpcodeExtension>>> = RwLock::new(Vec::new());
+
+/// Registers a handler for both the emulator and the disassembler
+pub fn register(extension: impl OpcodeExtension + 'static) {
+    EXTENSIONS.write().unwrap().push(Arc::new(extension));
+}
+
+/// Returns the handler of an opcode the built-in decoder doesn't claim
+pub fn find(opcode: u16) -> Option<Arc<dyn OpcodeExtension>> {
+    let extensions = EXTENSIONS.read().unwrap();
+    if extensions.is_empty() || disassembler::is_builtin(opcode) {
+        return None;
+    }
+    extensions.iter().find(|extension| extension.claims(opcode)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 5XY1 adds VY to VX without touching VF
+    struct AddWithoutCarry;
+
+    impl OpcodeExtension for AddWithoutCarry {
+        fn claims(&self, opcode: u16) -> bool {
+            opcode >> 12 == 5 && opcode & 0xF == 1
+        }
+
+        fn mnemonic(&self, opcode: u16) -> String {
+            format!("ADDNC V{:X}, V{:X}", (opcode & 0xF00) >> 8, (opcode & 0xF0) >> 4)
+        }
+
+        fn execute(&self, opcode: u16, internals: &mut C8, _gbuf: &mut [u8; 64*32]) -> String {
+            let (x, y) = (((opcode & 0xF00) >> 8) as usize, ((opcode & 0xF0) >> 4) as usize);
+            internals.V[x] = internals.V[x].wrapping_add(internals.V[y]);
+            format!("Adding V{:X} to V{:X} without a carry", y, x)
+        }
+    }
+
+    #[test]
+    fn registered_extensions_are_disassembled_and_executed() {
+        register(AddWithoutCarry);
+
+        assert!(find(0x5121).is_some());
+        assert!(find(0x5120).is_none(), "built-in opcodes can't be claimed");
+        assert!(find(0x5123).is_none());
+        assert_eq!(disassembler::disassemble(0x5121), "ADDNC V1, V2");
+        assert_eq!(disassembler::disassemble(0x5120), "SE V1, V2");
+
+        let mut internals = C8::default();
+        internals.V[1] = 0xF0;
+        internals.V[2] = 0x20;
+        internals.PC += 2;
+        let description = internals.execute(0x5121, &mut [0; 64*32], &[false; 16]);
+        assert_eq!(description, "Adding V2 to V1 without a carry");
+        assert_eq!(internals.V[1], 0x10);
+        assert_eq!(internals.V[0xF], 0);
+        assert_eq!(internals.cycles, 1);
+    }
+}
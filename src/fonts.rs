@@ -0,0 +1,143 @@
+use std::fs;
+
+/// Bytes of a single glyph of the small font
+pub const SMALL_GLYPH_HEIGHT: usize = 5;
+/// Bytes of a single glyph of the large font
+pub const LARGE_GLYPH_HEIGHT: usize = 10;
+/// Address the font gets loaded at unless another one is configured
+pub const DEFAULT_FONT_ADDRESS: u16 = 0;
+
+/// Fonts of the historical interpreters
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontSet {
+    CosmacVip,
+    Eti660,
+    Dream6800,
+    FishNChips,
+    SuperChip,
+    /// Loaded from a font file
+    Custom,
+}
+
+impl FontSet {
+    pub const ALL: [FontSet; 6] = [FontSet::CosmacVip, FontSet::Eti660, FontSet::Dream6800, FontSet::FishNChips, FontSet::SuperChip, FontSet::Custom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FontSet::CosmacVip => "COSMAC VIP",
+            FontSet::Eti660 => "ETI-660",
+            FontSet::Dream6800 => "DREAM 6800",
+            FontSet::FishNChips => "FISH'N'CHIPS",
+            FontSet::SuperChip => "SUPER-CHIP",
+            FontSet::Custom => "Custom",
+        }
+    }
+}
+
+const COSMAC_VIP: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x60, 0x20, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80, 0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x10, 0x10, 0x10,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0, 0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xF0, 0x50, 0x70, 0x50, 0xF0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0, 0xF0, 0x50, 0x50, 0x50, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+const ETI_660: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, 0x20, 0x20, 0x20, 0x20, 0x20, 0xE0, 0x20, 0xE0, 0x80, 0xE0, 0xE0, 0x20, 0xE0, 0x20, 0xE0,
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, 0xE0, 0x80, 0xE0, 0x20, 0xE0, 0xE0, 0x80, 0xE0, 0xA0, 0xE0, 0xE0, 0x20, 0x20, 0x20, 0x20,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, 0xE0, 0xA0, 0xE0, 0x20, 0xE0, 0xE0, 0xA0, 0xE0, 0xA0, 0xA0, 0x80, 0x80, 0xE0, 0xA0, 0xE0,
+    0xE0, 0x80, 0x80, 0x80, 0xE0, 0x20, 0x20, 0xE0, 0xA0, 0xE0, 0xE0, 0x80, 0xE0, 0x80, 0xE0, 0xE0, 0x80, 0xC0, 0x80, 0x80,
+];
+
+const DREAM_6800: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, 0x40, 0x40, 0x40, 0x40, 0x40, 0xE0, 0x20, 0xE0, 0x80, 0xE0, 0xE0, 0x20, 0xE0, 0x20, 0xE0,
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, 0xE0, 0x80, 0xE0, 0x20, 0xE0, 0xE0, 0x80, 0xE0, 0xA0, 0xE0, 0xE0, 0x20, 0x20, 0x20, 0x20,
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, 0xE0, 0xA0, 0xE0, 0x20, 0xE0, 0xE0, 0xA0, 0xE0, 0xA0, 0xA0, 0xC0, 0xA0, 0xE0, 0xA0, 0xC0,
+    0xE0, 0x80, 0x80, 0x80, 0xE0, 0xC0, 0xA0, 0xA0, 0xA0, 0xC0, 0xE0, 0x80, 0xE0, 0x80, 0xE0, 0xE0, 0x80, 0xC0, 0x80, 0x80,
+];
+
+const FISH_N_CHIPS: [u8; 80] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, 0x40, 0xC0, 0x40, 0x40, 0xE0, 0xC0, 0x20, 0x40, 0x80, 0xE0, 0xC0, 0x20, 0x40, 0x20, 0xC0,
+    0x20, 0xA0, 0xE0, 0x20, 0x20, 0xE0, 0x80, 0xC0, 0x20, 0xC0, 0x40, 0x80, 0xC0, 0xA0, 0x40, 0xE0, 0x20, 0x60, 0x40, 0x40,
+    0x40, 0xA0, 0x40, 0xA0, 0x40, 0x40, 0xA0, 0x60, 0x20, 0x40, 0x40, 0xA0, 0xE0, 0xA0, 0xA0, 0xC0, 0xA0, 0xC0, 0xA0, 0xC0,
+    0x60, 0x80, 0x80, 0x80, 0x60, 0xC0, 0xA0, 0xA0, 0xA0, 0xC0, 0xE0, 0x80, 0xC0, 0x80, 0xE0, 0xE0, 0x80, 0xC0, 0x80, 0x80,
+];
+
+/// Digits 0 to 9 of the large SUPER-CHIP font used by FX30
+const SUPER_CHIP_LARGE: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// Glyphs FX29 and FX30 point I at. The large font is stored right behind the small one.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; 16 * SMALL_GLYPH_HEIGHT],
+    /// Up to 16 large glyphs, empty if the font has none
+    pub large: Vec<u8>,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::preset(FontSet::SuperChip)
+    }
+}
+
+impl Font {
+    /// Font of the given interpreter, the custom font set starts out as a copy of the default font
+    pub fn preset(font_set: FontSet) -> Self {
+        let small = match font_set {
+            FontSet::CosmacVip => COSMAC_VIP,
+            FontSet::Eti660 => ETI_660,
+            FontSet::Dream6800 => DREAM_6800,
+            FontSet::FishNChips => FISH_N_CHIPS,
+            FontSet::SuperChip | FontSet::Custom => crate::emulator::FONTSET,
+        };
+        let large = match font_set {
+            FontSet::SuperChip | FontSet::Custom => SUPER_CHIP_LARGE.to_vec(),
+            _ => vec![],
+        };
+        Self { small, large }
+    }
+
+    /// Reads a font file, the 80 bytes of the small font optionally followed by the large font
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 16 * SMALL_GLYPH_HEIGHT {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "a font file needs at least 80 bytes"));
+        }
+        let mut small = [0u8; 16 * SMALL_GLYPH_HEIGHT];
+        let small_len = small.len();
+        small.clone_from_slice(&bytes[..small_len]);
+        let large_len = (bytes.len() - small_len).min(16 * LARGE_GLYPH_HEIGHT);
+        let large = bytes[small_len..small_len + large_len].to_vec();
+        Ok(Self { small, large })
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut bytes = self.small.to_vec();
+        bytes.extend_from_slice(&self.large);
+        fs::write(path, bytes)
+    }
+
+    /// Bytes the font takes up in memory
+    pub fn len(&self) -> usize {
+        self.small.len() + self.large.len()
+    }
+
+    /// Rows of a glyph, None if the font doesn't have it
+    pub fn glyph_mut(&mut self, digit: usize, large: bool) -> Option<&mut [u8]> {
+        if large {
+            self.large.get_mut(digit * LARGE_GLYPH_HEIGHT..(digit + 1) * LARGE_GLYPH_HEIGHT)
+        }else{
+            self.small.get_mut(digit * SMALL_GLYPH_HEIGHT..(digit + 1) * SMALL_GLYPH_HEIGHT)
+        }
+    }
+}
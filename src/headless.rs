@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use crate::analyzer::Analysis;
+use crate::capture;
+use crate::coverage::Coverage;
+use crate::database::Database;
+use crate::detector;
+use crate::fonts::{self, Font, FontSet};
+use crate::machine_code;
+use crate::profiler::Profiler;
+use crate::emulator::{C8, DEFAULT_INSTRUCTIONS_PER_FRAME};
+use crate::machine::{MachineConfig, MemorySize};
+use crate::palette::{Palette, PaletteSet};
+use crate::postprocess::{PostProcessing, PostProcessor};
+use crate::vip_timing::{Operands, VipClock};
+
+const USAGE: &str = "usage: chip8-emulator --headless <rom> [--cycles <n>] [--ipf <instructions per frame>] [--vip-timing] [--trap-machine-code] [--font <font set or file>] [--font-address <address>] [--program-start <address>] [--memory <2K|4K|64K>] [--stack-depth <n>] [--fill <byte>] [--coverage <listing file>] [--profile <folded stacks file>] [--cfg <dot file>] [--screenshot <png or ppm file>] [--screenshot-scale <n>] [--palette <palette>]";
+
+/// Options for running a rom without opening any windows
+struct HeadlessOptions {
+    target_file: String,
+    cycles: u64,
+    instructions_per_frame: Option<u32>,
+    vip_timing: bool,
+    trap_machine_code: bool,
+    font: Font,
+    font_address: u16,
+    machine_config: MachineConfig,
+    coverage_file: Option<String>,
+    profile_file: Option<String>,
+    cfg_file: Option<String>,
+    screenshot_file: Option<String>,
+    screenshot_scale: usize,
+    /// Palette picked by name, the database's colours or black and white otherwise
+    palette: Option<Palette>,
+}
+
+impl HeadlessOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut args = args.iter();
+        let target_file = args.next().ok_or(USAGE)?.clone();
+        let mut options = Self { target_file, cycles: 100_000, instructions_per_frame: None, vip_timing: false, trap_machine_code: false, font: Font::default(), font_address: fonts::DEFAULT_FONT_ADDRESS, machine_config: MachineConfig::default(), coverage_file: None, profile_file: None, cfg_file: None, screenshot_file: None, screenshot_scale: 1, palette: None };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--vip-timing" => { options.vip_timing = true; continue; },
+                "--trap-machine-code" => { options.trap_machine_code = true; continue; },
+                _ => {}
+            }
+            let value = args.next().ok_or(format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "--cycles" => options.cycles = value.parse().map_err(|_| format!("invalid cycle count: {}", value))?,
+                "--ipf" => options.instructions_per_frame = Some(value.parse().map_err(|_| format!("invalid instructions per frame: {}", value))?),
+                "--font" => {
+                    // font sets are picked by name, anything else is read as a font file
+                    options.font = match FontSet::ALL.iter().find(|font_set| font_set.name().eq_ignore_ascii_case(value)) {
+                        Some(font_set) => Font::preset(*font_set),
+                        None => Font::load(value).map_err(|e| format!("{}: {}", value, e))?,
+                    };
+                },
+                "--font-address" => options.font_address = parse_hex(value).ok_or(format!("invalid font address: {}", value))?,
+                "--program-start" => options.machine_config.program_start = parse_hex(value).ok_or(format!("invalid program start: {}", value))?,
+                "--memory" => {
+                    options.machine_config.memory_size = *MemorySize::ALL.iter()
+                        .find(|size| size.name().eq_ignore_ascii_case(value))
+                        .ok_or(format!("invalid memory size: {}", value))?;
+                },
+                "--stack-depth" => options.machine_config.stack_depth = value.parse().map_err(|_| format!("invalid stack depth: {}", value))?,
+                "--fill" => options.machine_config.fill = parse_hex(value).and_then(|fill| u8::try_from(fill).ok()).ok_or(format!("invalid fill byte: {}", value))?,
+                "--coverage" => options.coverage_file = Some(value.clone()),
+                "--profile" => options.profile_file = Some(value.clone()),
+                "--cfg" => options.cfg_file = Some(value.clone()),
+                "--screenshot" => options.screenshot_file = Some(value.clone()),
+                "--screenshot-scale" => options.screenshot_scale = value.parse().map_err(|_| format!("invalid screenshot scale: {}", value))?,
+                "--palette" => {
+                    let palette_set = PaletteSet::ALL.iter()
+                        .find(|palette_set| palette_set.name().eq_ignore_ascii_case(value))
+                        .ok_or(format!("invalid palette: {}", value))?;
+                    options.palette = Some(Palette::preset(*palette_set));
+                },
+                _ => return Err(format!("unknown option: {}\n{}", arg, USAGE)),
+            }
+        }
+        // checked once all options are known since the memory size can come after the program start
+        let config = &options.machine_config;
+        if config.program_start as usize >= config.memory_len() {
+            return Err(format!("program start 0x{:03X} is outside of {} memory", config.program_start, config.memory_size.name()));
+        }
+        Ok(options)
+    }
+}
+
+/// Parses a hexadecimal number with or without a 0x prefix
+fn parse_hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+/// Runs the program for the requested amount of cycles with no keys pressed,
+/// stops early if the program waits for a keypress since it would never continue.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = HeadlessOptions::parse(args)?;
+
+    let mut internals = C8::new(options.machine_config);
+    let program = internals.load_program(&options.target_file).map_err(|e| format!("{}: {}", options.target_file, e))?;
+    internals.font_address = options.font_address;
+    internals.load_font(&options.font);
+    let (program_start, program_len) = (program.start, program.len());
+    let mut coverage = Coverage::new(program_start, program_len, internals.memory.len());
+    let analysis = Analysis::analyze(&internals.memory, program_start, program_len);
+
+    let detection = detector::detect(&internals.memory, &analysis);
+    println!("suggested platform: {}", detection.platform.name());
+    for reason in detection.reasons.iter() {
+        println!("    {}", reason);
+    }
+    let mut profiler = Profiler::new(program_start as u16);
+
+    let mut gbuf = [0u8; 64*32];
+    let key_states = [false; 16];
+    let mut instructions_per_frame = DEFAULT_INSTRUCTIONS_PER_FRAME;
+    let mut palette = Palette::default();
+
+    if let Some(entry) = Database::load().lookup(&internals.memory[program]) {
+        println!("database entry: {} ({})", entry.title, entry.platform_name);
+        internals.quirks = entry.quirks;
+        if let Some(tickrate) = entry.tickrate {
+            instructions_per_frame = tickrate;
+        }
+        if let Some(colors) = entry.colors {
+            palette = colors;
+        }
+    }
+    let palette = options.palette.unwrap_or(palette);
+    let instructions_per_frame = options.instructions_per_frame.unwrap_or(instructions_per_frame).max(1);
+    let mut vip_clock = VipClock::default();
+    internals.machine_code.trap_unhandled = options.trap_machine_code;
+    let mut unhandled_machine_code = BTreeMap::new();
+
+    let mut executed = 0u64;
+    'frames: while executed < options.cycles {
+        if options.vip_timing {
+            vip_clock.start_frame();
+        }
+        let mut frame_executed = 0;
+        while executed < options.cycles {
+            if options.vip_timing && !vip_clock.has_cycles() || !options.vip_timing && frame_executed >= instructions_per_frame {
+                break;
+            }
+            if internals.key_wait.is_some() {
+                break 'frames;
+            }
+
+            if internals.pc_out_of_memory() {
+                break 'frames;
+            }
+            let opcode = internals.fetch();
+            let old_pc = internals.PC;
+            let operands = Operands::capture(opcode, &internals);
+            coverage.record(old_pc);
+            profiler.record(opcode);
+            if internals.advance_pc() {
+                break 'frames;
+            }
+            internals.execute(opcode, &mut gbuf, &key_states);
+            executed += 1;
+            frame_executed += 1;
+
+            if let Some(target) = machine_code::call_target(opcode) {
+                if internals.machine_code.get(target).is_none() {
+                    unhandled_machine_code.entry(target).or_insert(old_pc);
+                }
+            }
+            if internals.fault.is_some() {
+                break 'frames;
+            }
+
+            if options.vip_timing {
+                vip_clock.charge(opcode, operands, &internals);
+            }else if internals.quirks.display_wait && opcode >> 12 == 0xD {
+                break;
+            }
+        }
+
+        internals.delay_timer = internals.delay_timer.saturating_sub(1);
+        internals.sound_timer = internals.sound_timer.saturating_sub(1);
+        // nothing shows the sprites of a frame here
+        internals.sprite_draws.clear();
+    }
+
+    if let Some(fault) = &internals.fault {
+        println!("stopped after {} cycles: {}", executed, fault);
+    }else if internals.key_wait.is_some() {
+        println!("stopped after {} cycles, waiting for keypress", executed);
+    }else{
+        println!("executed {} cycles", executed);
+    }
+
+    for (target, caller) in unhandled_machine_code.iter() {
+        println!("warning: unhandled machine code call to 0x{:03X} (first from 0x{:03X})", target, caller);
+    }
+
+    let (covered, total) = coverage.summary();
+    println!("coverage: {}/{} instructions ({:.2}%)", covered, total, coverage.percentage());
+
+    if let Some(coverage_file) = &options.coverage_file {
+        coverage.write_listing(coverage_file, &internals.memory).map_err(|e| format!("{}: {}", coverage_file, e))?;
+        println!("listing written to {}", coverage_file);
+    }
+
+    if let Some(cfg_file) = &options.cfg_file {
+        analysis.write_dot(cfg_file, &internals.memory).map_err(|e| format!("{}: {}", cfg_file, e))?;
+        println!("control flow graph written to {}", cfg_file);
+    }
+
+    if let Some(profile_file) = &options.profile_file {
+        profiler.write_folded_stacks(profile_file).map_err(|e| format!("{}: {}", profile_file, e))?;
+        println!("folded stacks written to {}", profile_file);
+    }
+
+    if let Some(screenshot_file) = &options.screenshot_file {
+        let post_processing = PostProcessing::default();
+        let frame = PostProcessor::default().process(&gbuf, &palette, &post_processing);
+        capture::save_screenshot(screenshot_file, &frame, options.screenshot_scale, &post_processing).map_err(|e| format!("{}: {}", screenshot_file, e))?;
+        println!("screenshot written to {}", screenshot_file);
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+//! CHIP-8 emulator with a debugger ui, `run` starts it the same way the chip8-emulator binary does.
+//! Opcode extensions registered with `extensions::register` before that show up in the emulator and the disassembler.
+
+mod emulator_ui;
+use emulator_ui::EmulatorUI;
+
+pub mod emulator;
+mod disassembler;
+mod coverage;
+mod profiler;
+mod analyzer;
+mod quirks;
+mod detector;
+mod database;
+mod scheduler;
+mod vip_timing;
+mod cdp1802;
+mod vip;
+mod machine_code;
+pub mod extensions;
+mod fonts;
+mod machine;
+mod display;
+mod palette;
+mod postprocess;
+mod capture;
+mod recording;
+mod sprites;
+mod headless;
+
+/// Runs the emulator with the command line arguments, `--headless` runs a rom without any windows
+pub fn run(args: &[String]) {
+    if args.len() > 1 && args[1] == "--headless" {
+        if let Err(error) = headless::run(&args[2..]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut options = eframe::NativeOptions::default();
+    let [width, height] = display::DisplaySettings::load().ui_window_size;
+    options.initial_window_size = Some(egui::vec2(width, height));
+
+    eframe::run_native(
+        "CHIP-8 Emulator",
+        options,
+        Box::new(
+            |_cc| Box::new(EmulatorUI::default())
+        ));
+}
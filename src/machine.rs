@@ -0,0 +1,62 @@
+use crate::emulator::PROGRAM_START;
+
+/// Amounts of memory the interpreters had available
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemorySize {
+    TwoK,
+    FourK,
+    /// XO-CHIP
+    SixtyFourK,
+}
+
+impl MemorySize {
+    pub const ALL: [MemorySize; 3] = [MemorySize::TwoK, MemorySize::FourK, MemorySize::SixtyFourK];
+
+    pub fn bytes(&self) -> usize {
+        match self {
+            MemorySize::TwoK => 0x800,
+            MemorySize::FourK => 0x1000,
+            MemorySize::SixtyFourK => 0x10000,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemorySize::TwoK => "2K",
+            MemorySize::FourK => "4K",
+            MemorySize::SixtyFourK => "64K",
+        }
+    }
+}
+
+/// Memory layout of the emulated machine, the default matches how this emulator has always behaved
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MachineConfig {
+    /// Address programs get loaded at and start running from
+    pub program_start: u16,
+    pub memory_size: MemorySize,
+    /// Subroutine calls that can be nested before the stack overflows
+    pub stack_depth: usize,
+    /// Value memory holds before the font and the program are loaded
+    pub fill: u8,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self { program_start: PROGRAM_START as u16, memory_size: MemorySize::FourK, stack_depth: 16, fill: 0 }
+    }
+}
+
+impl MachineConfig {
+    /// Named layouts of historical machines
+    pub const PRESETS: [(&'static str, MachineConfig); 4] = [
+        ("COSMAC VIP", MachineConfig { program_start: 0x200, memory_size: MemorySize::FourK, stack_depth: 12, fill: 0 }),
+        ("ETI-660", MachineConfig { program_start: 0x600, memory_size: MemorySize::FourK, stack_depth: 16, fill: 0 }),
+        ("SUPER-CHIP", MachineConfig { program_start: 0x200, memory_size: MemorySize::FourK, stack_depth: 16, fill: 0 }),
+        ("XO-CHIP", MachineConfig { program_start: 0x200, memory_size: MemorySize::SixtyFourK, stack_depth: 16, fill: 0 }),
+    ];
+
+    pub fn memory_len(&self) -> usize {
+        self.memory_size.bytes()
+    }
+}
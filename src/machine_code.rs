@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use crate::emulator::C8;
+
+/// Rust implementation of a machine code routine a program calls with 0NNN
+pub type Routine = fn(&mut C8, &mut [u8; 64*32]);
+
+/// Routines that can be picked for a call target in the ui
+pub const BUILT_IN_ROUTINES: [(&str, Routine); 3] = [
+    ("Ignore", ignore),
+    ("Clear screen", clear_screen),
+    ("Stop with a fault", fault),
+];
+
+fn ignore(_internals: &mut C8, _gbuf: &mut [u8; 64*32]) {}
+
+fn clear_screen(_internals: &mut C8, gbuf: &mut [u8; 64*32]) {
+    gbuf.clone_from(&[0; 64*32]);
+}
+
+fn fault(internals: &mut C8, _gbuf: &mut [u8; 64*32]) {
+    let call = internals.PC.wrapping_sub(2) as usize;
+    let target = ((internals.memory[call] as u16) << 8 | internals.memory[call + 1] as u16) & 0xFFF;
+    internals.fault = Some(format!("Machine code routine at 0x{:03X} trapped", target));
+}
+
+/// Returns the target of a 0NNN machine code call, 00E0 and 00EE aren't calls
+pub fn call_target(opcode: u16) -> Option<u16> {
+    if opcode >> 12 == 0 && !matches!(opcode & 0xFF, 0xE0 | 0xEE) {
+        Some(opcode & 0xFFF)
+    }else{
+        None
+    }
+}
+
+/// Rust routines that stand in for the machine code at the addresses programs call with 0NNN
+#[derive(Clone, Default)]
+pub struct MachineCode {
+    routines: BTreeMap<u16, (String, Routine)>,
+    /// Calls to addresses without a routine stop the emulator instead of being ignored
+    pub trap_unhandled: bool,
+}
+
+impl MachineCode {
+    /// Runs the routine whenever a program calls the given address
+    pub fn register(&mut self, address: u16, name: &str, routine: Routine) {
+        self.routines.insert(address & 0xFFF, (name.to_owned(), routine));
+    }
+
+    pub fn unregister(&mut self, address: u16) {
+        self.routines.remove(&(address & 0xFFF));
+    }
+
+    pub fn get(&self, address: u16) -> Option<&(String, Routine)> {
+        self.routines.get(&address)
+    }
+
+    /// Addresses that have a routine along with the routine's name
+    pub fn routines(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.routines.iter().map(|(address, (name, _))| (*address, name.as_str()))
+    }
+}
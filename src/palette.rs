@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+/// Palettes picked for single roms are kept here, keyed by the SHA-1 of the rom
+pub const OVERRIDES_FILE: &str = "palettes.json";
+/// What each colour of a palette is used for
+pub const COLOR_NAMES: [&str; 4] = ["Background", "Foreground", "Plane 2", "Both planes"];
+
+/// Built-in palettes, the ones taken from Octo make use of all four XO-CHIP colours
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteSet {
+    BlackAndWhite,
+    GreenPhosphor,
+    Amber,
+    Lcd,
+    Octo,
+    OctoLcd,
+    Hotdog,
+    Cga0,
+    Cga1,
+    /// Edited in the ui or picked for the loaded rom
+    Custom,
+}
+
+impl PaletteSet {
+    pub const ALL: [PaletteSet; 10] = [
+        PaletteSet::BlackAndWhite, PaletteSet::GreenPhosphor, PaletteSet::Amber, PaletteSet::Lcd, PaletteSet::Octo,
+        PaletteSet::OctoLcd, PaletteSet::Hotdog, PaletteSet::Cga0, PaletteSet::Cga1, PaletteSet::Custom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteSet::BlackAndWhite => "Black and white",
+            PaletteSet::GreenPhosphor => "Green phosphor",
+            PaletteSet::Amber => "Amber",
+            PaletteSet::Lcd => "LCD",
+            PaletteSet::Octo => "Octo",
+            PaletteSet::OctoLcd => "Octo LCD",
+            PaletteSet::Hotdog => "Hotdog",
+            PaletteSet::Cga0 => "CGA 0",
+            PaletteSet::Cga1 => "CGA 1",
+            PaletteSet::Custom => "Custom",
+        }
+    }
+}
+
+/// Colours the screen is drawn with. The value of a pixel picks its colour, 0 is the background and 1 the foreground.
+/// 2 and 3 are kept for the second XO-CHIP plane and pixels set in both planes, nothing draws them until XO-CHIP planes are emulated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::preset(PaletteSet::BlackAndWhite)
+    }
+}
+
+impl Palette {
+    /// Colours of a built-in palette, the custom palette starts out black and white
+    pub fn preset(palette_set: PaletteSet) -> Self {
+        let colors = match palette_set {
+            PaletteSet::BlackAndWhite | PaletteSet::Custom => [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+            PaletteSet::GreenPhosphor => [0x001A00, 0x33FF33, 0x1A991A, 0xB3FFB3],
+            PaletteSet::Amber => [0x1A0F00, 0xFFB000, 0x996600, 0xFFE099],
+            PaletteSet::Lcd => [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F],
+            PaletteSet::Octo => [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+            PaletteSet::OctoLcd => [0xF9FFB3, 0x3D8026, 0xABCC47, 0x00131A],
+            PaletteSet::Hotdog => [0x000000, 0xFF0000, 0xFFFF00, 0xFFFFFF],
+            PaletteSet::Cga0 => [0x000000, 0x00FF00, 0xFF0000, 0xFFFF00],
+            PaletteSet::Cga1 => [0x000000, 0xFF00FF, 0x00FFFF, 0xFFFFFF],
+        };
+        Self { colors: colors.map(|rgb: u32| [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]) }
+    }
+
+    /// Colour of a pixel of the screen buffer
+    pub fn color(&self, pixel: u8) -> [u8; 3] {
+        self.colors[(pixel & 3) as usize]
+    }
+}
+
+/// Palettes the user picked for single roms, they win over the built-in palette and the database's colours
+#[derive(Clone, Default)]
+pub struct PaletteOverrides {
+    palettes: HashMap<String, Palette>,
+}
+
+impl PaletteOverrides {
+    pub fn load() -> Self {
+        let palettes = match fs::read_to_string(OVERRIDES_FILE) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("{}: {}", OVERRIDES_FILE, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self { palettes }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.palettes).map_err(|e| e.to_string())?;
+        fs::write(OVERRIDES_FILE, json).map_err(|e| format!("{}: {}", OVERRIDES_FILE, e))
+    }
+
+    pub fn get(&self, sha1: &str) -> Option<Palette> {
+        self.palettes.get(sha1).copied()
+    }
+
+    /// Picks the palette for the rom with the given hash and writes the overrides to disk
+    pub fn set(&mut self, sha1: &str, palette: Palette) -> Result<(), String> {
+        self.palettes.insert(sha1.to_owned(), palette);
+        self.save()
+    }
+
+    pub fn remove(&mut self, sha1: &str) -> Result<(), String> {
+        self.palettes.remove(sha1);
+        self.save()
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::TIMER_FREQUENCY;
+use crate::palette::Palette;
+
+/// Screen after post-processing, one colour per CHIP-8 pixel
+pub type Frame = [[u8; 3]; 64*32];
+/// Largest scale frames get blown up to before they are handed to the output
+pub const MAX_SCALE: usize = 16;
+/// Scanlines and the pixel grid leave this much of a pixel's brightness
+const OVERLAY_BRIGHTNESS: f32 = 0.5;
+
+/// Filters applied between the screen buffer and the output, all of them run on the CPU
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct PostProcessing {
+    /// Every frame is shown mixed half and half with the one before it
+    pub blend_frames: bool,
+    /// Pixels stay lit for this many frames after they were cleared, hides sprites that get erased and redrawn
+    pub hold_frames: u32,
+    /// Milliseconds it takes a cleared pixel to fade to half its brightness, 0 clears pixels right away
+    pub phosphor_half_life: u32,
+    /// Darkens the bottom row of every pixel
+    pub scanlines: bool,
+    /// Darkens the right column of every pixel as well
+    pub pixel_grid: bool,
+}
+
+/// Keeps what the filters need to know about the frames shown before
+pub struct PostProcessor {
+    /// Colour every pixel was shown in last frame before blending
+    shown: [[f32; 3]; 64*32],
+    /// Frames since every pixel was last lit along with the value it was lit with
+    lit: [(u32, u8); 64*32],
+}
+
+impl Default for PostProcessor {
+    fn default() -> Self {
+        Self { shown: [[0f32; 3]; 64*32], lit: [(u32::MAX, 0); 64*32] }
+    }
+}
+
+impl PostProcessor {
+    /// Turns the screen buffer into the frame that gets shown, expected to be called once for every frame shown
+    pub fn process(&mut self, gbuf: &[u8; 64*32], palette: &Palette, settings: &PostProcessing) -> Frame {
+        let decay = if settings.phosphor_half_life > 0 {
+            let frame_time = 1000f32 / TIMER_FREQUENCY as f32;
+            0.5f32.powf(frame_time / settings.phosphor_half_life as f32)
+        }else{
+            0f32
+        };
+
+        let mut frame = [[0u8; 3]; 64*32];
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let mut value = gbuf[i];
+            if value != 0 {
+                self.lit[i] = (0, value);
+            }else{
+                self.lit[i].0 = self.lit[i].0.saturating_add(1);
+                if self.lit[i].0 <= settings.hold_frames {
+                    value = self.lit[i].1;
+                }
+            }
+
+            // lit pixels light up right away, cleared ones fade out towards the background
+            let target = palette.color(value).map(|channel| channel as f32);
+            let mut color = target;
+            if value == 0 {
+                for channel in 0..3 {
+                    color[channel] += (self.shown[i][channel] - target[channel]) * decay;
+                }
+            }
+
+            let mut output = color;
+            if settings.blend_frames {
+                for channel in 0..3 {
+                    output[channel] = (color[channel] + self.shown[i][channel]) / 2f32;
+                }
+            }
+            self.shown[i] = color;
+            *pixel = output.map(|channel| channel.round().clamp(0f32, 255f32) as u8);
+        }
+        frame
+    }
+}
+
+/// Blows the frame up by a whole number and applies the scanlines and the pixel grid, which need a scale of at least 2.
+/// Returns the pixels as RGB bytes, row by row.
+pub fn scale(frame: &Frame, scale: usize, settings: &PostProcessing) -> Vec<u8> {
+    let scale = scale.clamp(1, MAX_SCALE);
+    let width = 64 * scale;
+    let mut pixels = Vec::with_capacity(width * 32 * scale * 3);
+    for y in 0..32 * scale {
+        let scanline = settings.scanlines && scale > 1 && y % scale == scale - 1;
+        for x in 0..width {
+            let grid = settings.pixel_grid && scale > 1 && x % scale == scale - 1;
+            let color = frame[x / scale + y / scale * 64];
+            if scanline || grid {
+                pixels.extend(color.map(|channel| (channel as f32 * OVERLAY_BRIGHTNESS) as u8));
+            }else{
+                pixels.extend(color);
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = Palette { colors: [[0, 0, 0], [200, 100, 50], [0, 0, 255], [255, 255, 255]] };
+
+    /// Screen buffer with only the first pixel lit
+    fn lit_screen(lit: bool) -> [u8; 64*32] {
+        let mut gbuf = [0u8; 64*32];
+        gbuf[0] = lit as u8;
+        gbuf
+    }
+
+    /// First pixel of the frames processed one after the other
+    fn first_pixels(settings: PostProcessing, screens: &[bool]) -> Vec<[u8; 3]> {
+        let mut processor = PostProcessor::default();
+        screens.iter().map(|lit| processor.process(&lit_screen(*lit), &PALETTE, &settings)[0]).collect()
+    }
+
+    #[test]
+    fn without_filters_pixels_are_drawn_in_the_palette() {
+        let frame = PostProcessor::default().process(&lit_screen(true), &PALETTE, &PostProcessing::default());
+        assert_eq!(frame[0], [200, 100, 50]);
+        assert!(frame[1..].iter().all(|pixel| *pixel == [0, 0, 0]));
+        assert_eq!(first_pixels(PostProcessing::default(), &[true, false]), vec![[200, 100, 50], [0, 0, 0]]);
+    }
+
+    #[test]
+    fn blending_mixes_every_frame_with_the_one_before() {
+        let settings = PostProcessing { blend_frames: true, ..PostProcessing::default() };
+        assert_eq!(first_pixels(settings, &[true, true, false, false]), vec![[100, 50, 25], [200, 100, 50], [100, 50, 25], [0, 0, 0]]);
+    }
+
+    #[test]
+    fn held_pixels_stay_lit_for_the_hold_frames() {
+        let settings = PostProcessing { hold_frames: 2, ..PostProcessing::default() };
+        let pixels = first_pixels(settings, &[true, false, false, false, true]);
+        assert_eq!(pixels, vec![[200, 100, 50], [200, 100, 50], [200, 100, 50], [0, 0, 0], [200, 100, 50]]);
+    }
+
+    #[test]
+    fn cleared_pixels_fade_with_the_half_life() {
+        let settings = PostProcessing { phosphor_half_life: 100, ..PostProcessing::default() };
+        let pixels = first_pixels(settings, &[true, false, false]);
+        let decay = 0.5f32.powf(1000f32 / TIMER_FREQUENCY as f32 / 100f32);
+        assert_eq!(pixels[1][0], (200f32 * decay).round() as u8);
+        assert_eq!(pixels[2][0], (200f32 * decay * decay).round() as u8);
+        assert!(pixels[2][0] < pixels[1][0] && pixels[1][0] < 200);
+    }
+
+    #[test]
+    fn scaling_multiplies_the_dimensions() {
+        let frame = PostProcessor::default().process(&lit_screen(true), &PALETTE, &PostProcessing::default());
+        for (requested, actual) in [(0, 1), (1, 1), (3, 3), (100, MAX_SCALE)] {
+            assert_eq!(scale(&frame, requested, &PostProcessing::default()).len(), 64 * actual * 32 * actual * 3);
+        }
+        let pixels = scale(&frame, 2, &PostProcessing::default());
+        // the lit pixel covers 2x2 output pixels
+        assert_eq!(&pixels[..6], &[200, 100, 50, 200, 100, 50]);
+        assert_eq!(&pixels[128 * 3..128 * 3 + 6], &[200, 100, 50, 200, 100, 50]);
+        assert_eq!(&pixels[6..9], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn scanlines_and_the_grid_darken_the_last_row_and_column() {
+        let frame = PostProcessor::default().process(&lit_screen(true), &PALETTE, &PostProcessing::default());
+        let settings = PostProcessing { scanlines: true, pixel_grid: true, ..PostProcessing::default() };
+        let pixels = scale(&frame, 2, &settings);
+        let pixel = |x: usize, y: usize| &pixels[(y * 128 + x) * 3..(y * 128 + x) * 3 + 3];
+        assert_eq!(pixel(0, 0), &[200, 100, 50]);
+        assert_eq!(pixel(1, 0), &[100, 50, 25]);
+        assert_eq!(pixel(0, 1), &[100, 50, 25]);
+        assert_eq!(pixel(1, 1), &[100, 50, 25]);
+        // a scale of 1 has no room for either
+        assert_eq!(&scale(&frame, 1, &settings)[..3], &[200, 100, 50]);
+    }
+}
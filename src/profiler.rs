@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+use crate::emulator::PROGRAM_START;
+
+/// Cycles spent in a single subroutine
+#[derive(Clone, Copy, Default)]
+pub struct RoutineStats {
+    /// Entry address of the subroutine, the program entry point stands for the main routine
+    pub address: u16,
+    pub calls: u64,
+    /// Cycles spent executing the subroutine's own instructions
+    pub self_cycles: u64,
+    /// Cycles spent in the subroutine and everything it called
+    pub inclusive_cycles: u64,
+}
+
+/// Column the profiler table is sorted by
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProfilerSort {
+    Address,
+    Calls,
+    SelfCycles,
+    InclusiveCycles,
+}
+
+/// Attributes executed cycles to subroutines by following the 2NNN/00EE call structure
+#[derive(Clone)]
+pub struct Profiler {
+    /// Entry addresses of the subroutines that are currently active, starting with the main routine
+    call_stack: Vec<u16>,
+    /// Cycles spent with every distinct call stack
+    folded: HashMap<Vec<u16>, u64>,
+    calls: HashMap<u16, u64>,
+    total_cycles: u64,
+    /// Address the program starts at, cycles spent outside of subroutines belong to it
+    main: u16,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new(PROGRAM_START as u16)
+    }
+}
+
+impl Profiler {
+    pub fn new(program_start: u16) -> Self {
+        Self {
+            call_stack: vec![program_start],
+            folded: HashMap::new(),
+            calls: HashMap::new(),
+            total_cycles: 0,
+            main: program_start,
+        }
+    }
+
+    /// Registers an instruction that is about to be executed, the cycle belongs to the subroutine
+    /// that is active before the instruction changes the call stack
+    pub fn record(&mut self, opcode: u16) {
+        self.total_cycles += 1;
+        match self.folded.get_mut(self.call_stack.as_slice()) {
+            Some(cycles) => *cycles += 1,
+            None => { self.folded.insert(self.call_stack.clone(), 1); },
+        }
+
+        if opcode >> 12 == 2 {
+            let nnn = opcode & 0xFFF;
+            self.call_stack.push(nnn);
+            *self.calls.entry(nnn).or_insert(0) += 1;
+        }else if opcode == 0x00EE && self.call_stack.len() > 1 {
+            self.call_stack.pop();
+        }
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    /// Name used for a subroutine in the table and in the exported stacks
+    pub fn routine_name(&self, address: u16) -> String {
+        if address == self.main {
+            "main".to_owned()
+        }else{
+            format!("sub_{:03X}", address)
+        }
+    }
+
+    /// Collects the statistics of every subroutine that was executed at least once
+    pub fn routines(&self, sort: ProfilerSort, descending: bool) -> Vec<RoutineStats> {
+        let mut routines: HashMap<u16, RoutineStats> = HashMap::new();
+        for (stack, cycles) in self.folded.iter() {
+            for (i, address) in stack.iter().enumerate() {
+                // recursive calls appear more than once in the stack but should only be counted once
+                if stack[..i].contains(address) {
+                    continue;
+                }
+                let routine = routines.entry(*address).or_insert(RoutineStats { address: *address, ..Default::default() });
+                routine.inclusive_cycles += cycles;
+            }
+            let routine = routines.get_mut(stack.last().unwrap()).unwrap();
+            routine.self_cycles += cycles;
+        }
+        for routine in routines.values_mut() {
+            routine.calls = *self.calls.get(&routine.address).unwrap_or(&0);
+        }
+
+        let mut routines: Vec<RoutineStats> = routines.into_values().collect();
+        routines.sort_by_key(|routine| match sort {
+            ProfilerSort::Address => routine.address as u64,
+            ProfilerSort::Calls => routine.calls,
+            ProfilerSort::SelfCycles => routine.self_cycles,
+            ProfilerSort::InclusiveCycles => routine.inclusive_cycles,
+        });
+        if descending {
+            routines.reverse();
+        }
+        routines
+    }
+
+    /// Builds the profile in the folded stack format used by flamegraph tools,
+    /// one line per call stack with its frames separated by semicolons followed by the cycle count
+    pub fn folded_stacks(&self) -> String {
+        let mut lines: Vec<String> = self.folded.iter()
+            .map(|(stack, cycles)| {
+                let frames: Vec<String> = stack.iter().map(|address| self.routine_name(*address)).collect();
+                format!("{} {}", frames.join(";"), cycles)
+            })
+            .collect();
+        lines.sort();
+        let mut folded = lines.join("\n");
+        folded.push('\n');
+        folded
+    }
+
+    pub fn write_folded_stacks(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.folded_stacks().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Main calls 0x300 twice, 0x300 calls 0x400 on the second call
+    fn profile() -> Profiler {
+        let mut profiler = Profiler::new(0x200);
+        for opcode in [0x6000, 0x2300, 0x6001, 0x00EE, 0x2300, 0x2400, 0x6002, 0x6003, 0x00EE, 0x00EE, 0x1200] {
+            profiler.record(opcode);
+        }
+        profiler
+    }
+
+    fn routine(profiler: &Profiler, address: u16) -> RoutineStats {
+        *profiler.routines(ProfilerSort::Address, false).iter().find(|routine| routine.address == address).unwrap()
+    }
+
+    #[test]
+    fn cycles_are_counted_per_routine() {
+        let profiler = profile();
+        assert_eq!(profiler.total_cycles(), 11);
+
+        let main = routine(&profiler, 0x200);
+        assert_eq!((main.calls, main.self_cycles, main.inclusive_cycles), (0, 4, 11));
+        let outer = routine(&profiler, 0x300);
+        assert_eq!((outer.calls, outer.self_cycles, outer.inclusive_cycles), (2, 4, 7));
+        let inner = routine(&profiler, 0x400);
+        assert_eq!((inner.calls, inner.self_cycles, inner.inclusive_cycles), (1, 3, 3));
+    }
+
+    #[test]
+    fn recursion_is_only_counted_once_inclusively() {
+        let mut profiler = Profiler::new(0x200);
+        for opcode in [0x2300, 0x2300, 0x6000, 0x00EE, 0x00EE] {
+            profiler.record(opcode);
+        }
+        let routine = routine(&profiler, 0x300);
+        assert_eq!((routine.calls, routine.self_cycles, routine.inclusive_cycles), (2, 4, 4));
+    }
+
+    #[test]
+    fn returning_with_an_empty_call_stack_stays_in_main() {
+        let mut profiler = Profiler::new(0x200);
+        profiler.record(0x00EE);
+        profiler.record(0x6000);
+        assert_eq!(routine(&profiler, 0x200).self_cycles, 2);
+    }
+
+    #[test]
+    fn routines_are_sorted() {
+        let profiler = profile();
+        let addresses: Vec<u16> = profiler.routines(ProfilerSort::SelfCycles, true).iter().map(|routine| routine.address).collect();
+        assert_eq!(addresses.last(), Some(&0x400));
+        let addresses: Vec<u16> = profiler.routines(ProfilerSort::Calls, false).iter().map(|routine| routine.address).collect();
+        assert_eq!(addresses, vec![0x200, 0x400, 0x300]);
+    }
+
+    #[test]
+    fn folded_stacks_name_every_frame() {
+        assert_eq!(profile().folded_stacks(), "main 4\nmain;sub_300 4\nmain;sub_300;sub_400 3\n");
+    }
+}
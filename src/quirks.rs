@@ -0,0 +1,56 @@
+/// Machines the emulated program can be targeting
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Platform {
+    /// Original COSMAC VIP interpreter
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+/// Behaviours that differ between interpreters, the default matches how this emulator has always behaved
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// FX55 and FX65 leave I pointing past the last register that was stored or loaded
+    pub memory_increment: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX
+    pub shifting: bool,
+    /// BNNN jumps to NNN + VX instead of NNN + V0
+    pub jumping: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping around
+    pub clipping: bool,
+    /// DXYN waits for the next frame before drawing, so at most one sprite gets drawn per frame
+    pub display_wait: bool,
+    /// FX0A completes when the pressed key is released instead of as soon as it's pressed
+    pub key_release: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self { vf_reset: true, memory_increment: false, shifting: true, jumping: false, clipping: false, display_wait: false, key_release: false }
+    }
+}
+
+impl Quirks {
+    /// Quirks of the reference interpreter of the given platform
+    pub fn preset(platform: Platform) -> Self {
+        match platform {
+            Platform::Chip8 => Self { vf_reset: true, memory_increment: true, shifting: false, jumping: false, clipping: true, display_wait: true, key_release: true },
+            Platform::SuperChip => Self { vf_reset: false, memory_increment: false, shifting: true, jumping: true, clipping: true, display_wait: false, key_release: false },
+            Platform::XoChip => Self { vf_reset: false, memory_increment: true, shifting: false, jumping: false, clipping: false, display_wait: false, key_release: false },
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::emulator::TIMER_FREQUENCY;
+use crate::postprocess::{self, Frame, PostProcessing};
+
+/// Sample rate of the audio sidecar
+const SAMPLE_RATE: u32 = 44100;
+/// Pitch of the square wave the beeper plays while the sound timer is running
+const BEEPER_FREQUENCY: u32 = 440;
+const BEEPER_VOLUME: i16 = 8192;
+/// Browsers slow down GIF frames shorter than this many hundredths of a second, so faster changes get dropped
+const GIF_MIN_DELAY: u32 = 2;
+
+/// Formats gameplay can be recorded in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VideoFormat {
+    /// Animated GIF, frames that don't change are merged
+    Gif,
+    /// Uncompressed YUV4MPEG2 stream at exactly 60 frames per second
+    Y4m,
+}
+
+impl VideoFormat {
+    pub const ALL: [VideoFormat; 2] = [VideoFormat::Gif, VideoFormat::Y4m];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoFormat::Gif => "GIF",
+            VideoFormat::Y4m => "Y4M",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Y4m => "y4m",
+        }
+    }
+}
+
+/// Writes every frame the emulator runs to a video file and optionally the beeper to a WAV file next to it
+pub struct Recorder {
+    path: String,
+    format: VideoFormat,
+    video: BufWriter<File>,
+    scale: usize,
+    /// Frames recorded so far
+    frames: u32,
+    /// Last GIF frame, written once it's known how long it's shown for, along with the frame it was recorded at
+    gif_pending: Option<(Vec<u8>, u32)>,
+    audio: Option<BufWriter<File>>,
+    /// Samples written to the audio file so far
+    samples: u32,
+}
+
+impl Recorder {
+    /// Creates the video file at the path, the audio file gets the same name with a .wav extension
+    pub fn start(path: &str, format: VideoFormat, scale: usize, record_audio: bool) -> std::io::Result<Self> {
+        if let Some(directory) = Path::new(path).parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let scale = scale.clamp(1, postprocess::MAX_SCALE);
+        let (width, height) = (64 * scale as u16, 32 * scale as u16);
+
+        let mut video = BufWriter::new(File::create(path)?);
+        match format {
+            VideoFormat::Gif => {
+                video.write_all(b"GIF89a")?;
+                // no global colour table, every frame brings its own
+                video.write_all(&width.to_le_bytes())?;
+                video.write_all(&height.to_le_bytes())?;
+                video.write_all(&[0, 0, 0])?;
+                // loops forever
+                video.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+            },
+            VideoFormat::Y4m => {
+                writeln!(video, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, TIMER_FREQUENCY)?;
+            },
+        }
+
+        let audio = if record_audio {
+            let mut audio = BufWriter::new(File::create(Path::new(path).with_extension("wav"))?);
+            Recorder::write_wav_header(&mut audio, 0)?;
+            Some(audio)
+        }else{
+            None
+        };
+
+        Ok(Self { path: path.to_owned(), format, video, scale, frames: 0, gif_pending: None, audio, samples: 0 })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Adds a frame along with a frame worth of audio, `beeping` is true while the sound timer runs
+    pub fn record(&mut self, frame: &Frame, post_processing: &PostProcessing, beeping: bool) -> std::io::Result<()> {
+        let pixels = postprocess::scale(frame, self.scale, post_processing);
+        match self.format {
+            VideoFormat::Gif => {
+                let changed = match &self.gif_pending {
+                    Some((pending, _)) => *pending != pixels,
+                    None => true,
+                };
+                if changed {
+                    // a frame that isn't shown for long enough gets replaced instead
+                    let shown_long_enough = self.gif_pending.as_ref().is_none_or(|(_, start)| self.gif_delay(*start, self.frames) >= GIF_MIN_DELAY);
+                    if shown_long_enough {
+                        self.flush_gif_frame()?;
+                        self.gif_pending = Some((pixels, self.frames));
+                    }else if let Some((pending, _)) = &mut self.gif_pending {
+                        *pending = pixels;
+                    }
+                }
+            },
+            VideoFormat::Y4m => {
+                self.video.write_all(b"FRAME\n")?;
+                let planes = Recorder::yuv_planes(&pixels);
+                for plane in planes.iter() {
+                    self.video.write_all(plane)?;
+                }
+            },
+        }
+        self.frames += 1;
+
+        if let Some(audio) = &mut self.audio {
+            let end = (self.frames as u64 * SAMPLE_RATE as u64 / TIMER_FREQUENCY as u64) as u32;
+            let half_period = SAMPLE_RATE / BEEPER_FREQUENCY / 2;
+            for sample in self.samples..end {
+                let value = if !beeping {0} else if (sample / half_period) & 1 == 0 {BEEPER_VOLUME} else {-BEEPER_VOLUME};
+                audio.write_all(&value.to_le_bytes())?;
+            }
+            self.samples = end;
+        }
+        Ok(())
+    }
+
+    /// Writes whatever is still pending and fixes up the headers, returns the amount of frames recorded
+    pub fn finish(mut self) -> std::io::Result<u32> {
+        if self.format == VideoFormat::Gif {
+            self.flush_gif_frame()?;
+            self.video.write_all(&[0x3B])?;
+        }
+        self.video.flush()?;
+
+        if let Some(mut audio) = self.audio.take() {
+            audio.seek(SeekFrom::Start(0))?;
+            Recorder::write_wav_header(&mut audio, self.samples)?;
+            audio.flush()?;
+        }
+        Ok(self.frames)
+    }
+
+    /// Hundredths of a second between two frames
+    fn gif_delay(&self, start: u32, end: u32) -> u32 {
+        let time = |frame: u32| (frame as f32 * 100f32 / TIMER_FREQUENCY as f32).round() as u32;
+        time(end) - time(start)
+    }
+
+    fn flush_gif_frame(&mut self) -> std::io::Result<()> {
+        let (pixels, start) = match self.gif_pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let delay = self.gif_delay(start, self.frames).max(1) as u16;
+        let (width, height) = (64 * self.scale as u16, 32 * self.scale as u16);
+        let (table, indices) = Recorder::color_table(&pixels);
+
+        // graphic control extension with the delay, then the image descriptor with a local colour table of 256 entries
+        self.video.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.video.write_all(&delay.to_le_bytes())?;
+        self.video.write_all(&[0x00, 0x00])?;
+        self.video.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.video.write_all(&width.to_le_bytes())?;
+        self.video.write_all(&height.to_le_bytes())?;
+        self.video.write_all(&[0x87])?;
+        self.video.write_all(&table)?;
+
+        self.video.write_all(&[8])?;
+        for block in Recorder::lzw(&indices).chunks(255) {
+            self.video.write_all(&[block.len() as u8])?;
+            self.video.write_all(block)?;
+        }
+        self.video.write_all(&[0])
+    }
+
+    /// Colour table of 256 entries and the index of every pixel, colours lose precision until there are few enough of them
+    fn color_table(pixels: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut mask = 0xFFu8;
+        loop {
+            let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+            let mut table = Vec::with_capacity(256 * 3);
+            let mut indices = Vec::with_capacity(pixels.len() / 3);
+            for rgb in pixels.chunks(3) {
+                let color = [rgb[0] & mask, rgb[1] & mask, rgb[2] & mask];
+                let next = colors.len();
+                if next == 256 && !colors.contains_key(&color) {
+                    break;
+                }
+                let index = *colors.entry(color).or_insert_with(|| {
+                    table.extend_from_slice(&color);
+                    next as u8
+                });
+                indices.push(index);
+            }
+            if indices.len() == pixels.len() / 3 {
+                table.resize(256 * 3, 0);
+                return (table, indices);
+            }
+            mask <<= 1;
+        }
+    }
+
+    /// LZW compression as GIF uses it, with 8 bit symbols and codes of up to 12 bits
+    fn lzw(indices: &[u8]) -> Vec<u8> {
+        const CLEAR: u16 = 256;
+        const END: u16 = 257;
+        let mut output = Vec::new();
+        let (mut buffer, mut bits) = (0u32, 0u32);
+        let mut emit = |code: u16, width: u32, output: &mut Vec<u8>| {
+            buffer |= (code as u32) << bits;
+            bits += width;
+            while bits >= 8 {
+                output.push(buffer as u8);
+                buffer >>= 8;
+                bits -= 8;
+            }
+        };
+
+        let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+        let mut next_code = END + 1;
+        let mut width = 9;
+        emit(CLEAR, width, &mut output);
+
+        let mut current: Option<u16> = None;
+        for &index in indices {
+            let prefix = match current {
+                Some(prefix) => prefix,
+                None => {
+                    current = Some(index as u16);
+                    continue;
+                },
+            };
+            if let Some(&code) = dictionary.get(&(prefix, index)) {
+                current = Some(code);
+                continue;
+            }
+            emit(prefix, width, &mut output);
+            if next_code < 4096 {
+                dictionary.insert((prefix, index), next_code);
+                next_code += 1;
+                if next_code > 1 << width && width < 12 {
+                    width += 1;
+                }
+            }else{
+                emit(CLEAR, width, &mut output);
+                dictionary.clear();
+                next_code = END + 1;
+                width = 9;
+            }
+            current = Some(index as u16);
+        }
+        if let Some(code) = current {
+            emit(code, width, &mut output);
+        }
+        emit(END, width, &mut output);
+        if bits > 0 {
+            output.push(buffer as u8);
+        }
+        output
+    }
+
+    /// Splits RGB pixels into full resolution Y, U and V planes using BT.601
+    fn yuv_planes(pixels: &[u8]) -> [Vec<u8>; 3] {
+        let mut planes = [Vec::with_capacity(pixels.len() / 3), Vec::with_capacity(pixels.len() / 3), Vec::with_capacity(pixels.len() / 3)];
+        for rgb in pixels.chunks(3) {
+            let (r, g, b) = (rgb[0] as f32, rgb[1] as f32, rgb[2] as f32);
+            planes[0].push((16f32 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8);
+            planes[1].push((128f32 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8);
+            planes[2].push((128f32 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8);
+        }
+        planes
+    }
+
+    /// Header of a 16 bit mono PCM file holding the given amount of samples
+    fn write_wav_header(audio: &mut impl Write, samples: u32) -> std::io::Result<()> {
+        let data_len = samples * 2;
+        audio.write_all(b"RIFF")?;
+        audio.write_all(&(36 + data_len).to_le_bytes())?;
+        audio.write_all(b"WAVEfmt ")?;
+        audio.write_all(&16u32.to_le_bytes())?;
+        audio.write_all(&1u16.to_le_bytes())?;
+        audio.write_all(&1u16.to_le_bytes())?;
+        audio.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        audio.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        audio.write_all(&2u16.to_le_bytes())?;
+        audio.write_all(&16u16.to_le_bytes())?;
+        audio.write_all(b"data")?;
+        audio.write_all(&data_len.to_le_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// GIF LZW decoder going by the spec, returns the symbols and how many CLEAR codes it read
+    fn unlzw(data: &[u8]) -> (Vec<u8>, usize) {
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u32, data.iter());
+        let mut width = 9;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut output, mut clears) = (Vec::new(), 0);
+        loop {
+            while bits < width {
+                buffer |= (*bytes.next().expect("stream ends before the END code") as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << width) - 1)) as usize;
+            buffer >>= width;
+            bits -= width;
+            match code {
+                256 => {
+                    table = (0..=255).map(|symbol| vec![symbol]).chain([vec![], vec![]]).collect();
+                    width = 9;
+                    previous = None;
+                    clears += 1;
+                    continue;
+                },
+                257 => break,
+                _ => {},
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    assert_eq!(code, table.len(), "code from beyond the dictionary");
+                    [previous.as_slice(), &previous[..1]].concat()
+                },
+                (None, None) => panic!("first code after a CLEAR isn't a symbol"),
+            };
+            if let Some(previous) = previous.take() {
+                if table.len() < 4096 {
+                    table.push([previous.as_slice(), &entry[..1]].concat());
+                }
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+        (output, clears)
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("chip8-recording-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn lzw_round_trips_through_every_code_width_and_a_full_dictionary() {
+        // noise hardly repeats, so nearly every symbol adds a code and the dictionary fills up several times
+        let mut seed = 1u32;
+        let indices: Vec<u8> = (0..20000).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect();
+        let (decoded, clears) = unlzw(&Recorder::lzw(&indices));
+        assert_eq!(decoded, indices);
+        assert!(clears >= 3, "only {} CLEAR codes", clears);
+    }
+
+    #[test]
+    fn lzw_round_trips_repetitive_data() {
+        let indices: Vec<u8> = (0..64*32*4).map(|i| (i / 7 % 3) as u8).collect();
+        let (decoded, clears) = unlzw(&Recorder::lzw(&indices));
+        assert_eq!(decoded, indices);
+        assert_eq!(clears, 1);
+        assert!(unlzw(&Recorder::lzw(&[])).0.is_empty());
+    }
+
+    #[test]
+    fn wav_header_describes_the_data() {
+        let mut header = Vec::new();
+        Recorder::write_wav_header(&mut header, 100).unwrap();
+        assert_eq!(header.len(), 44);
+        assert_eq!(&header[4..8], &(36u32 + 200).to_le_bytes());
+        assert_eq!(&header[24..28], &SAMPLE_RATE.to_le_bytes());
+        assert_eq!(&header[40..44], &200u32.to_le_bytes());
+    }
+
+    #[test]
+    fn y4m_and_wav_lengths_match_the_frames_recorded() {
+        let path = temp_path("lengths.y4m");
+        let mut recorder = Recorder::start(&path, VideoFormat::Y4m, 2, true).unwrap();
+        let frame: Frame = [[0xFF, 0x80, 0x00]; 64*32];
+        for i in 0..3 {
+            recorder.record(&frame, &PostProcessing::default(), i == 1).unwrap();
+        }
+        assert_eq!(recorder.finish().unwrap(), 3);
+
+        let video = fs::read(&path).unwrap();
+        let header = format!("YUV4MPEG2 W128 H64 F{}:1 Ip A1:1 C444\n", TIMER_FREQUENCY);
+        assert!(video.starts_with(header.as_bytes()));
+        assert_eq!(video.len(), header.len() + 3 * (b"FRAME\n".len() + 3 * 128 * 64));
+
+        let wav_path = Path::new(&path).with_extension("wav");
+        let audio = fs::read(&wav_path).unwrap();
+        let samples = 3 * SAMPLE_RATE / TIMER_FREQUENCY;
+        assert_eq!(audio.len() as u32, 44 + samples * 2);
+        assert_eq!(&audio[4..8], &(36 + samples * 2).to_le_bytes());
+        assert_eq!(&audio[40..44], &(samples * 2).to_le_bytes());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&wav_path).unwrap();
+    }
+
+    #[test]
+    fn gif_frames_decode_to_the_recorded_pixels() {
+        let path = temp_path("frames.gif");
+        let mut recorder = Recorder::start(&path, VideoFormat::Gif, 1, false).unwrap();
+        let mut frame: Frame = [[0, 0, 0]; 64*32];
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            // exactly 256 colours, so none of them lose precision
+            *pixel = [(i * 7 % 16 * 16) as u8, (i / 64 % 16 * 16) as u8, 0x40];
+        }
+        for _ in 0..4 {
+            recorder.record(&frame, &PostProcessing::default(), false).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let gif = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(gif.last(), Some(&0x3B));
+        // header, looping extension, graphic control extension, image descriptor, colour table and LZW code size
+        let image = 13 + 19 + 8 + 10 + 256 * 3 + 1;
+        assert_eq!(gif[13 + 19 + 4], 7, "4 unchanged frames at 60 fps are shown as one for 7 hundredths of a second");
+        let mut data = Vec::new();
+        let mut blocks = &gif[image..];
+        while blocks[0] != 0 {
+            let len = blocks[0] as usize;
+            data.extend_from_slice(&blocks[1..=len]);
+            blocks = &blocks[len + 1..];
+        }
+        assert_eq!(blocks, &[0, 0x3B], "one image only");
+
+        let table = &gif[image - 1 - 256 * 3..image - 1];
+        let (indices, _) = unlzw(&data);
+        let decoded: Vec<[u8; 3]> = indices.iter().map(|&index| {
+            let color = &table[index as usize * 3..index as usize * 3 + 3];
+            [color[0], color[1], color[2]]
+        }).collect();
+        assert_eq!(decoded, frame.to_vec());
+    }
+}
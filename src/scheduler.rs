@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+/// Frames that are run back to back after a stall before the remaining ones get dropped
+const MAX_CATCH_UP_FRAMES: u32 = 5;
+/// How often the measured speed gets updated
+const MEASURE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Fixed step clock, keeps track of when frames are due with sub-millisecond precision
+/// and measures the speed the emulator actually runs at
+pub struct Scheduler {
+    frame_duration: Duration,
+    /// Point in time the next frame is due at
+    next_frame: Instant,
+
+    measure_start: Instant,
+    measured_frames: u32,
+    measured_instructions: u64,
+    /// Frames per second over the last measure interval
+    pub fps: f32,
+    /// Instructions per second over the last measure interval
+    pub ips: f32,
+}
+
+impl Scheduler {
+    pub fn new(frequency: f32) -> Self {
+        let now = Instant::now();
+        Self {
+            frame_duration: Duration::from_secs_f32(1f32 / frequency),
+            next_frame: now,
+            measure_start: now,
+            measured_frames: 0,
+            measured_instructions: 0,
+            fps: 0f32,
+            ips: 0f32,
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let frame_duration = Duration::from_secs_f32(1f32 / frequency);
+        if frame_duration != self.frame_duration {
+            // a frame that got shorter shouldn't have to wait out the rest of the longer one
+            self.frame_duration = frame_duration;
+            self.next_frame = self.next_frame.min(Instant::now() + frame_duration);
+        }
+    }
+
+    /// Returns how many frames have to be run to catch up with the clock.
+    /// After long stalls at most MAX_CATCH_UP_FRAMES are returned and the rest is skipped.
+    pub fn due_frames(&mut self, now: Instant) -> u32 {
+        let mut frames = 0;
+        while self.next_frame <= now && frames < MAX_CATCH_UP_FRAMES {
+            self.next_frame += self.frame_duration;
+            frames += 1;
+        }
+        if self.next_frame <= now {
+            self.next_frame = now + self.frame_duration;
+        }
+        frames
+    }
+
+    /// Moves the clock forward without waiting, used while running unthrottled
+    pub fn skip_to(&mut self, now: Instant) {
+        self.next_frame = now + self.frame_duration;
+    }
+
+    /// Time left until the next frame is due
+    pub fn until_next_frame(&self, now: Instant) -> Duration {
+        self.next_frame.saturating_duration_since(now)
+    }
+
+    /// Registers a frame that was run along with the amount of instructions it executed
+    pub fn record_frame(&mut self, instructions: u32) {
+        self.measured_frames += 1;
+        self.measured_instructions += instructions as u64;
+    }
+
+    /// Updates the measured speed once a measure interval has passed, returns true if it was updated
+    pub fn measure(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.measure_start);
+        if elapsed < MEASURE_INTERVAL {
+            return false;
+        }
+        self.fps = self.measured_frames as f32 / elapsed.as_secs_f32();
+        self.ips = self.measured_instructions as f32 / elapsed.as_secs_f32();
+        self.measured_frames = 0;
+        self.measured_instructions = 0;
+        self.measure_start = now;
+        true
+    }
+}
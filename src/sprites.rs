@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+
+use crate::capture;
+use crate::emulator::C8;
+use crate::palette::Palette;
+
+/// Sprites in every row of the sprite sheet
+pub const SHEET_COLUMNS: usize = 8;
+
+/// Shapes memory can be cut up into
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpriteSize {
+    /// 8 pixels wide and up to 15 rows high, a byte for every row like DXYN draws them
+    Small,
+    /// 16x16 sprites with two bytes for every row like SUPER-CHIP draws them with DXY0
+    Large,
+}
+
+impl SpriteSize {
+    pub const ALL: [SpriteSize; 2] = [SpriteSize::Small, SpriteSize::Large];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpriteSize::Small => "8 wide",
+            SpriteSize::Large => "16x16",
+        }
+    }
+}
+
+/// Part of memory viewed as a row of sprites laid out back to back
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SpriteSheet {
+    /// Address of the first sprite
+    pub address: u16,
+    pub size: SpriteSize,
+    /// Rows of every small sprite
+    pub height: usize,
+    /// Amount of sprites
+    pub count: usize,
+}
+
+impl Default for SpriteSheet {
+    fn default() -> Self {
+        Self { address: 0x200, size: SpriteSize::Small, height: 8, count: 32 }
+    }
+}
+
+impl SpriteSheet {
+    pub fn width(&self) -> usize {
+        match self.size {
+            SpriteSize::Small => 8,
+            SpriteSize::Large => 16,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        match self.size {
+            SpriteSize::Small => self.height,
+            SpriteSize::Large => 16,
+        }
+    }
+
+    pub fn sprite_len(&self) -> usize {
+        self.rows() * self.width() / 8
+    }
+
+    pub fn sprite_address(&self, index: usize) -> usize {
+        self.address as usize + index * self.sprite_len()
+    }
+
+    /// Address of the byte a pixel of a sprite is stored in
+    pub fn byte_address(&self, index: usize, x: usize, y: usize) -> usize {
+        self.sprite_address(index) + y * self.width() / 8 + x / 8
+    }
+
+    /// Returns true if the pixel is set, memory past the end reads as unset
+    pub fn pixel(&self, memory: &[u8], index: usize, x: usize, y: usize) -> bool {
+        memory.get(self.byte_address(index, x, y)).is_some_and(|byte| byte >> (7 - x % 8) & 1 == 1)
+    }
+
+    /// Draws the sprites next to each other, `SHEET_COLUMNS` to a row, in the background and foreground colours.
+    /// Returns the width, the height and the pixels as RGB bytes.
+    pub fn render(&self, memory: &[u8], sprites: &[usize], palette: &Palette) -> (u32, u32, Vec<u8>) {
+        let columns = sprites.len().clamp(1, SHEET_COLUMNS);
+        let lines = sprites.len().div_ceil(columns);
+        let (width, height) = (columns * self.width(), lines * self.rows());
+        let mut pixels = vec![0u8; width * height * 3];
+        for (i, rgb) in pixels.chunks_mut(3).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let color = match sprites.get(y / self.rows() * columns + x / self.width()) {
+                Some(&index) => palette.color(self.pixel(memory, index, x % self.width(), y % self.rows()) as u8),
+                None => palette.color(0),
+            };
+            rgb.copy_from_slice(&color);
+        }
+        (width as u32, height as u32, pixels)
+    }
+
+    /// Saves the sprites as a PNG sheet
+    pub fn export_png(&self, path: &str, memory: &[u8], sprites: &[usize], palette: &Palette) -> std::io::Result<()> {
+        let (width, height, pixels) = self.render(memory, sprites, palette);
+        fs::write(path, capture::encode_png(width, height, &pixels))
+    }
+}
+
+/// Writes a copy of the rom with the edited bytes that fall inside of it replaced, `edits` maps addresses to bytes.
+/// Returns how many edits were left out because they lie outside of the rom.
+pub fn write_patched_rom(rom_path: &str, patched_path: &str, program_start: u16, edits: &BTreeMap<u16, u8>) -> std::io::Result<usize> {
+    let mut rom = fs::read(rom_path)?;
+    let mut skipped = 0;
+    for (&address, &byte) in edits.iter() {
+        match (address as usize).checked_sub(program_start as usize).and_then(|offset| rom.get_mut(offset)) {
+            Some(target) => *target = byte,
+            None => skipped += 1,
+        }
+    }
+    fs::write(patched_path, rom)?;
+    Ok(skipped)
+}
+
+/// Memory the instruction at the program counter is about to draw if it's a DXYN with at least one row,
+/// DXY0 draws nothing until 16x16 sprites are emulated
+pub fn next_draw(internals: &C8) -> Option<Range<usize>> {
+    let opcode = internals.fetch();
+    let rows = (opcode & 0xF) as usize;
+    if opcode >> 12 != 0xD || rows == 0 {
+        return None;
+    }
+    Some(internals.I as usize..internals.I as usize + rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_draw(opcode: u16) -> C8 {
+        let mut internals = C8::default();
+        let pc = internals.PC as usize;
+        internals.memory[pc..pc + 2].clone_from_slice(&opcode.to_be_bytes());
+        internals.I = 0x300;
+        internals
+    }
+
+    #[test]
+    fn next_draw_covers_the_rows_drawn() {
+        assert_eq!(next_draw(&at_draw(0xD125)), Some(0x300..0x305));
+        assert_eq!(next_draw(&at_draw(0xD12F)), Some(0x300..0x30F));
+    }
+
+    #[test]
+    fn patched_rom_round_trips_the_edits_inside_of_it() {
+        let directory = std::env::temp_dir();
+        let rom_path = directory.join(format!("chip8-sprites-{}-rom.ch8", std::process::id()));
+        let patched_path = directory.join(format!("chip8-sprites-{}-patched.ch8", std::process::id()));
+        fs::write(&rom_path, [0x00, 0xE0, 0xF0, 0x90, 0xF0]).unwrap();
+
+        // one edit before the rom, two inside of it and one past its end
+        let edits = BTreeMap::from([(0x1FF, 0xAA), (0x202, 0x0F), (0x204, 0xFF), (0x205, 0xBB)]);
+        let skipped = write_patched_rom(rom_path.to_str().unwrap(), patched_path.to_str().unwrap(), 0x200, &edits).unwrap();
+        let patched = fs::read(&patched_path).unwrap();
+        let original = fs::read(&rom_path).unwrap();
+        fs::remove_file(&rom_path).unwrap();
+        fs::remove_file(&patched_path).unwrap();
+
+        assert_eq!(skipped, 2);
+        assert_eq!(patched, vec![0x00, 0xE0, 0x0F, 0x90, 0xFF]);
+        assert_eq!(original, vec![0x00, 0xE0, 0xF0, 0x90, 0xF0], "the rom itself is left alone");
+    }
+
+    #[test]
+    fn patching_a_missing_rom_fails() {
+        let missing = std::env::temp_dir().join(format!("chip8-sprites-{}-missing.ch8", std::process::id()));
+        let patched = std::env::temp_dir().join(format!("chip8-sprites-{}-unused.ch8", std::process::id()));
+        assert!(write_patched_rom(missing.to_str().unwrap(), patched.to_str().unwrap(), 0x200, &BTreeMap::new()).is_err());
+        assert!(!patched.exists(), "nothing gets written without the rom");
+    }
+
+    #[test]
+    fn nothing_is_drawn_by_dxy0_or_other_instructions() {
+        assert_eq!(next_draw(&at_draw(0xD120)), None);
+        assert_eq!(next_draw(&at_draw(0x6125)), None);
+    }
+}
@@ -0,0 +1,271 @@
+use std::fs;
+
+use crate::cdp1802::{Bus, Cdp1802};
+use crate::emulator::{C8, PROGRAM_START};
+use crate::vip_timing::MACHINE_CYCLES_PER_FRAME;
+
+/// RAM of a fully expanded VIP, mirrored up to the monitor ROM
+pub const RAM_SIZE: usize = 4096;
+/// The monitor ROM shows up at 0x8000 and above
+const MONITOR_START: u16 = 0x8000;
+/// Room for the interpreter below the program
+const INTERPRETER_SIZE: usize = PROGRAM_START;
+
+/// Machine cycles of a single line drawn by the CDP1861, 8 of them are DMA cycles
+const CYCLES_PER_LINE: u64 = 14;
+/// Lines the CDP1861 fetches from memory every frame
+const DISPLAY_LINES: u64 = 128;
+/// Line of the frame the display starts at
+const FIRST_DISPLAY_LINE: u64 = 80;
+/// The CDP1861 interrupts this many cycles before the first DMA, the interrupt routine has to be exactly as long
+const INTERRUPT_LEAD: u64 = 29;
+/// EF1 is asserted for 4 lines before the display starts and before it ends
+const EF1_LINES: u64 = 4;
+
+/// Everything connected to the CPU of the VIP
+struct VipBus {
+    ram: Vec<u8>,
+    monitor: Vec<u8>,
+    /// Key selected by OUT 2, EF3 reports whether it's pressed
+    key_latch: u8,
+    key_states: [bool; 16],
+    /// Turned on by INP 1 and off by OUT 1
+    display_on: bool,
+    /// EF1 as driven by the CDP1861
+    display_flag: bool,
+}
+
+impl Bus for VipBus {
+    fn read(&mut self, address: u16) -> u8 {
+        if address >= MONITOR_START {
+            self.monitor.get((address - MONITOR_START) as usize).copied().unwrap_or(0)
+        }else{
+            self.ram[address as usize % self.ram.len()]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < MONITOR_START {
+            let len = self.ram.len();
+            self.ram[address as usize % len] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn flag(&mut self, line: u8) -> bool {
+        match line {
+            1 => self.display_flag,
+            3 => self.key_states[self.key_latch as usize],
+            _ => false,
+        }
+    }
+}
+
+/// RCA COSMAC VIP running a CHIP-8 interpreter image on an emulated CDP1802 and CDP1861
+pub struct Vip {
+    pub cpu: Cdp1802,
+    bus: VipBus,
+    /// Cycle the current frame started at
+    frame_start: u64,
+    /// Lines fetched by the CDP1861 during the last frame, 8 bytes each
+    lines: [[u8; 8]; DISPLAY_LINES as usize],
+}
+
+impl Vip {
+    /// Loads the interpreter image at 0x0000 and the program at PROGRAM_START, the monitor ROM is optional
+    pub fn new(interpreter_file: &str, monitor_file: Option<&str>, target_file: &str) -> std::io::Result<Self> {
+        let mut ram = vec![0u8; RAM_SIZE];
+        let interpreter = fs::read(interpreter_file)?;
+        let interpreter_len = interpreter.len().min(INTERPRETER_SIZE);
+        ram[..interpreter_len].clone_from_slice(&interpreter[..interpreter_len]);
+
+        let program = fs::read(target_file)?;
+        let program_len = program.len().min(RAM_SIZE - PROGRAM_START);
+        ram[PROGRAM_START..PROGRAM_START + program_len].clone_from_slice(&program[..program_len]);
+
+        let monitor = match monitor_file {
+            Some(monitor_file) => fs::read(monitor_file)?,
+            None => vec![],
+        };
+
+        let mut cpu = Cdp1802::default();
+        cpu.reset();
+        // the monitor leaves the highest page of RAM in R1 before starting the program at 0x0000
+        cpu.R[1] = ((RAM_SIZE - 1) & 0xFF00) as u16;
+
+        let bus = VipBus { ram, monitor, key_latch: 0, key_states: [false; 16], display_on: false, display_flag: false };
+        Ok(Self { cpu, bus, frame_start: 0, lines: [[0; 8]; DISPLAY_LINES as usize] })
+    }
+
+    /// Runs the CPU for one frame of the CDP1861 with the given keys held down, returns the instructions executed
+    pub fn run_frame(&mut self, key_states: &[bool; 16]) -> u32 {
+        self.bus.key_states.clone_from(key_states);
+        let frame_end = self.frame_start + MACHINE_CYCLES_PER_FRAME as u64;
+        let nominal_display_start = self.frame_start + FIRST_DISPLAY_LINE * CYCLES_PER_LINE;
+        let interrupt_at = nominal_display_start - INTERRUPT_LEAD;
+
+        // the display gets lined up with the cycle the interrupt was actually taken at
+        let mut display_start: Option<u64> = None;
+        let mut next_line = 0;
+        let mut executed = 0;
+
+        if !self.bus.display_on {
+            self.lines = [[0; 8]; DISPLAY_LINES as usize];
+        }
+
+        while self.cpu.cycles < frame_end {
+            let cycle = self.cpu.cycles;
+
+            let start = display_start.unwrap_or(nominal_display_start);
+            self.bus.display_flag = (start - EF1_LINES * CYCLES_PER_LINE..start).contains(&cycle)
+                || (start + (DISPLAY_LINES - EF1_LINES) * CYCLES_PER_LINE..start + DISPLAY_LINES * CYCLES_PER_LINE).contains(&cycle);
+
+            if display_start.is_none() && self.bus.display_on && cycle >= interrupt_at && cycle < nominal_display_start && self.cpu.interrupt() {
+                display_start = Some(self.cpu.cycles - 1 + INTERRUPT_LEAD);
+                continue;
+            }
+
+            if let Some(start) = display_start {
+                if next_line < DISPLAY_LINES && cycle >= start + next_line * CYCLES_PER_LINE {
+                    for byte in 0..8 {
+                        self.lines[next_line as usize][byte] = self.cpu.dma_out(&mut self.bus);
+                    }
+                    next_line += 1;
+                    continue;
+                }
+            }
+
+            if self.cpu.idle {
+                // nothing happens until the next interrupt or DMA
+                let next_event = match display_start {
+                    Some(start) if next_line < DISPLAY_LINES => start + next_line * CYCLES_PER_LINE,
+                    None if self.bus.display_on && cycle < interrupt_at => interrupt_at,
+                    _ => frame_end,
+                };
+                self.cpu.cycles = next_event.clamp(cycle + 1, frame_end);
+                continue;
+            }
+
+            self.cpu.step(&mut self.bus);
+            executed += 1;
+        }
+
+        self.frame_start = frame_end;
+        executed
+    }
+
+    /// Every row of the CHIP-8 screen is shown on 4 lines, the last of which is picked in case the interrupt
+    /// routine repeats the rows one line later than expected
+    pub fn render(&self, gbuf: &mut [u8; 64*32]) {
+        for row in 0..32 {
+            let line = &self.lines[row * 4 + 3];
+            for column in 0..64 {
+                gbuf[row * 64 + column] = (line[column / 8] >> (7 - column % 8)) & 1;
+            }
+        }
+    }
+
+    /// Overwrites a byte of RAM, addresses past the end are ignored
+    pub fn write_memory(&mut self, address: u16, byte: u8) {
+        if let Some(target) = self.bus.ram.get_mut(address as usize) {
+            *target = byte;
+        }
+    }
+
+    /// Copies the state of the CHIP-8 interpreter into the shape the ui shows,
+    /// the interpreter keeps its program counter in R5, I in RA, the timers in R8 and the registers in the top page
+    pub fn chip8_state(&self, internals: &mut C8) {
+        internals.memory.clone_from(&self.bus.ram);
+        let registers = RAM_SIZE - 0x110;
+        internals.V.clone_from_slice(&self.bus.ram[registers..registers + 16]);
+        internals.PC = self.cpu.R[5];
+        internals.I = self.cpu.R[0xA];
+        internals.delay_timer = (self.cpu.R[8] >> 8) as u8;
+        internals.sound_timer = self.cpu.R[8] as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// VIP with the program in place of the interpreter, the display starts out off
+    fn vip(program: &[u8]) -> Vip {
+        let mut ram = vec![0u8; RAM_SIZE];
+        ram[..program.len()].clone_from_slice(program);
+        let mut cpu = Cdp1802::default();
+        cpu.reset();
+        let bus = VipBus { ram, monitor: vec![], key_latch: 0, key_states: [false; 16], display_on: false, display_flag: false };
+        Vip { cpu, bus, frame_start: 0, lines: [[0; 8]; DISPLAY_LINES as usize] }
+    }
+
+    #[test]
+    fn display_interrupt_dmas_a_frame_from_r0() {
+        let mut program = vec![
+            0xF8, 0x0F, 0xB2, 0xF8, 0x00, 0xA2, // R2 = 0x0F00, scratch for INP
+            0xF8, 0x00, 0xB1, 0xB3,             // R1.1 = R3.1 = 0
+            0xF8, 0x30, 0xA1,                   // R1 = 0x0030, the interrupt routine
+            0xF8, 0x12, 0xA3,                   // R3 = 0x0012
+            0xD3, 0x00,                         // SEP 3
+            0xE2, 0x69, 0x30, 0x14,             // SEX 2, INP 1 turns the display on, BR to itself
+        ];
+        program.resize(0x30, 0);
+        // the interrupt routine points R0 at the display data and waits for the DMA
+        program.extend_from_slice(&[0xF8, 0x01, 0xB0, 0xF8, 0x00, 0xA0, 0x30, 0x36]);
+        let mut vip = vip(&program);
+        // the last of the 4 lines of every row is shown, so row 0 is the 4th line of 8 bytes
+        vip.bus.ram[0x100 + 3 * 8] = 0x80;
+        vip.bus.ram[0x100 + 127 * 8 + 7] = 0x01;
+
+        vip.run_frame(&[false; 16]);
+        assert!(vip.bus.display_on);
+        assert_eq!(vip.cpu.R[0], 0x100 + DISPLAY_LINES as u16 * 8, "a DMA of 8 bytes for every line");
+        assert_eq!(vip.frame_start, MACHINE_CYCLES_PER_FRAME as u64);
+
+        let mut gbuf = [0u8; 64*32];
+        vip.render(&mut gbuf);
+        assert_eq!(gbuf[0], 1);
+        assert_eq!(gbuf[64*32 - 1], 1);
+        assert_eq!(gbuf.iter().filter(|pixel| **pixel != 0).count(), 2);
+    }
+
+    #[test]
+    fn chip8_state_reads_the_interpreter_registers() {
+        let vip_registers = (RAM_SIZE - 0x110) as u16;
+        let program = [
+            0xF8, 0x02, 0xB5, 0xF8, 0x34, 0xA5, // R5 = 0x0234, the CHIP-8 program counter
+            0xF8, 0x03, 0xBA, 0xF8, 0x45, 0xAA, // RA = 0x0345, I
+            0xF8, 0x12, 0xB8, 0xF8, 0x03, 0xA8, // R8 = 0x1203, the delay and sound timers
+            0xF8, (vip_registers >> 8) as u8, 0xB6, 0xF8, vip_registers as u8, 0xA6,
+            0xF8, 0xAB, 0x56,                   // V0 = 0xAB
+            0x16, 0xF8, 0xCD, 0x56,             // V1 = 0xCD
+            0x00,                               // IDL
+        ];
+        let mut vip = vip(&program);
+        vip.run_frame(&[false; 16]);
+        assert!(vip.cpu.idle);
+
+        let mut internals = C8::default();
+        vip.chip8_state(&mut internals);
+        assert_eq!(internals.PC, 0x234);
+        assert_eq!(internals.I, 0x345);
+        assert_eq!((internals.delay_timer, internals.sound_timer), (0x12, 0x03));
+        assert_eq!(internals.V[0], 0xAB);
+        assert_eq!(internals.V[1], 0xCD);
+        assert_eq!(internals.memory.len(), RAM_SIZE);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::emulator::C8;
+
+/// Machine cycles of the CDP1802 between two display interrupts, 8 clocks each at 1.76 MHz
+pub const MACHINE_CYCLES_PER_FRAME: u32 = 3668;
+/// Cycles of every frame taken up by the display DMA and the interrupt routine
+pub const DISPLAY_CYCLES_PER_FRAME: u32 = 1024 + 46;
+/// Cycles left for the interpreter every frame
+pub const INTERPRETER_CYCLES_PER_FRAME: u32 = MACHINE_CYCLES_PER_FRAME - DISPLAY_CYCLES_PER_FRAME;
+/// Cycles the interpreter loop spends fetching and decoding every instruction
+const FETCH_CYCLES: u32 = 40;
+
+/// State an instruction's cost depends on, captured before it runs since executing it can overwrite VX
+#[derive(Clone, Copy)]
+pub struct Operands {
+    /// Address the instruction was fetched from
+    pub pc: u16,
+    pub vx: u8,
+}
+
+impl Operands {
+    /// Expects the program counter to still point at the instruction
+    pub fn capture(opcode: u16, internals: &C8) -> Self {
+        Self { pc: internals.PC, vx: internals.V[((opcode & 0xF00) >> 8) as usize] }
+    }
+}
+
+/// Machine cycles the VIP interpreter needs for an instruction that was just executed,
+/// `internals` is the state after executing it
+pub fn instruction_cycles(opcode: u16, operands: Operands, internals: &C8) -> u32 {
+    let x = ((opcode & 0xF00) >> 8) as usize;
+    let n = (opcode & 0xF) as u32;
+    let skipped = internals.PC == operands.pc.wrapping_add(4);
+
+    let execution = match opcode >> 12 {
+        0 => match opcode {
+            0x00E0 => 3078,
+            0x00EE => 10,
+            _ => 0,
+        },
+        1 => 12,
+        2 => 26,
+        3 | 4 => if skipped {14} else {10},
+        5 | 9 => if skipped {18} else {14},
+        6 => 6,
+        7 => 10,
+        8 => if opcode & 0xF == 0 {12} else {44},
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        0xD => {
+            // sprites that aren't aligned to a byte have to be shifted into place row by row
+            let column = operands.vx as u32 % 8;
+            let row = if column == 0 {24} else {34 + 4 * column};
+            68 + n * row
+        },
+        0xE => if skipped {18} else {14},
+        0xF => match opcode & 0xFF {
+            0x07 | 0x0A | 0x15 | 0x18 => 10,
+            0x1E | 0x29 => 16,
+            0x33 => {
+                // digits are found by repeated subtraction
+                let value = operands.vx as u32;
+                84 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+            },
+            0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+            _ => 0,
+        },
+        _ => 0,
+    };
+    FETCH_CYCLES + execution
+}
+
+/// Hands out the machine cycles the interpreter gets every frame. Instructions are charged after they ran,
+/// so a frame can overrun its budget, the overrun is taken from the next frame.
+#[derive(Default)]
+pub struct VipClock {
+    budget: i64,
+}
+
+impl VipClock {
+    pub fn start_frame(&mut self) {
+        self.budget += INTERPRETER_CYCLES_PER_FRAME as i64;
+    }
+
+    /// Returns true while the current frame has cycles left
+    pub fn has_cycles(&self) -> bool {
+        self.budget > 0
+    }
+
+    /// Charges an instruction that was just executed. DXYN waits for the display interrupt before drawing,
+    /// so the rest of the frame is spent waiting and the drawing itself is charged to the next frame.
+    pub fn charge(&mut self, opcode: u16, operands: Operands, internals: &C8) {
+        if opcode >> 12 == 0xD {
+            self.wait();
+        }
+        self.budget -= instruction_cycles(opcode, operands, internals) as i64;
+    }
+
+    /// Gives up the rest of the current frame
+    pub fn wait(&mut self) {
+        self.budget = self.budget.min(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_cost_uses_the_column_from_before_the_draw() {
+        // DFE5 with the sprite 3 pixels into a byte, the draw leaves the collision flag in VF
+        let mut internals = C8::default();
+        internals.V[0xF] = 3;
+        let operands = Operands::capture(0xDFE5, &internals);
+        internals.V[0xF] = 1;
+        internals.PC += 2;
+        assert_eq!(instruction_cycles(0xDFE5, operands, &internals), FETCH_CYCLES + 68 + 5 * (34 + 4 * 3));
+
+        internals.V[0xF] = 0;
+        let aligned = Operands::capture(0xDFE5, &internals);
+        assert_eq!(instruction_cycles(0xDFE5, aligned, &internals), FETCH_CYCLES + 68 + 5 * 24);
+    }
+
+    #[test]
+    fn skips_cost_more_when_taken() {
+        let mut internals = C8::default();
+        let operands = Operands::capture(0x3000, &internals);
+        internals.PC += 4;
+        assert_eq!(instruction_cycles(0x3000, operands, &internals), FETCH_CYCLES + 14);
+        internals.PC -= 2;
+        assert_eq!(instruction_cycles(0x3000, operands, &internals), FETCH_CYCLES + 10);
+    }
+
+    #[test]
+    fn a_draw_gives_up_the_rest_of_the_frame() {
+        let internals = C8::default();
+        let mut clock = VipClock::default();
+        clock.start_frame();
+        clock.charge(0x6000, Operands::capture(0x6000, &internals), &internals);
+        assert!(clock.has_cycles());
+        clock.charge(0xD001, Operands::capture(0xD001, &internals), &internals);
+        assert!(!clock.has_cycles());
+        clock.start_frame();
+        assert_eq!(clock.budget, INTERPRETER_CYCLES_PER_FRAME as i64 - (FETCH_CYCLES + 68 + 24) as i64);
+    }
+}